@@ -0,0 +1,135 @@
+//! Benchmarks for the queries the game makes every frame, over the maps in www/data/maps.
+//! Run with cargo bench --no-default-features.
+
+use std::fs;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use wasm_ulmo_map::{MapDef, PlayMap, Rect, DEFAULT_TILE_SIZE};
+
+const MAPS: [&str; 3] = ["cave", "drops", "forest"];
+
+// the player's base rect, as in Player._initBaseRect with PlayConfig's defaultBaseRectWidth
+const BASE_RECT_WIDTH: u32 = 16;
+const BASE_RECT_HEIGHT: u32 = 18;
+const SPRITE_HEIGHT: u32 = 48;
+
+// MoveResult.deferral for shuffles and slides, and MapEvent's type for falling
+const DEFAULT_DEFERRAL: u8 = 1;
+const FALLING_EVENT: u8 = 1;
+
+// cases of each kind taken from a map, spread over the whole of it
+const MAX_CASES: usize = 64;
+
+// a move from a rect at a level
+type Case = (i8, i8, i8, Rect);
+
+struct Cases {
+    straight: Vec<Case>,
+    shuffle: Vec<Case>,
+    slide: Vec<Case>,
+    fall: Vec<(i8, Rect)>,
+    masks: Vec<(i8, Rect)>
+}
+
+fn load_map_def(name: &str) -> MapDef {
+    let json = fs::read_to_string(format!("www/data/maps/{}.json", name)).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+// the sprite's z as Sprites._updateZIndex works it out from the JS level, which is half the
+// doubled level passed here; PlayMap.getMasks doubles the level but not the z
+fn z_index(rect: &Rect, level: i8) -> i32 {
+    rect.bottom() + (level as i32 * DEFAULT_TILE_SIZE as i32).div_euclid(2)
+}
+
+fn spread<T: Copy>(cases: Vec<T>) -> Vec<T> {
+    let step = cases.len() / MAX_CASES + 1;
+    cases.into_iter().step_by(step).take(MAX_CASES).collect()
+}
+
+// Walks the base rect over every position and level on the map, sorting the moves the map
+// allows by what apply_move had to do to make them.
+fn find_cases(play_map: &PlayMap, rows: u32, cols: u32) -> Cases {
+    let (mut straight, mut shuffle, mut slide, mut fall, mut masks) = (vec![], vec![], vec![], vec![], vec![]);
+    let width = cols as i32 * DEFAULT_TILE_SIZE as i32 - BASE_RECT_WIDTH as i32;
+    let height = rows as i32 * DEFAULT_TILE_SIZE as i32 - BASE_RECT_HEIGHT as i32;
+    for level in (0..12).step_by(2) {
+        for y in (0..height).step_by(4) {
+            for x in (0..width).step_by(4) {
+                let rect = Rect::new(x, y, BASE_RECT_WIDTH, BASE_RECT_HEIGHT);
+                if !play_map.is_move_valid(level, rect).0 {
+                    continue;
+                }
+                if play_map.get_event(level, rect).is_some_and(|event| event.as_tuple().0 == FALLING_EVENT) {
+                    fall.push((level, rect));
+                }
+                let sprite_rect = Rect::new(x, y + BASE_RECT_HEIGHT as i32 - SPRITE_HEIGHT as i32, BASE_RECT_WIDTH, SPRITE_HEIGHT);
+                if !play_map.get_sprite_masks(sprite_rect, z_index(&sprite_rect, level), level, true).is_empty() {
+                    masks.push((level, sprite_rect));
+                }
+                for (mx, my) in [(2, 0), (-2, 0), (0, 2), (0, -2), (2, 2), (-2, -2), (2, -2), (-2, 2)] {
+                    let result = play_map.apply_move(mx, my, level, rect);
+                    if !result.valid {
+                        continue;
+                    }
+                    let case = (mx, my, level, rect);
+                    if result.deferral != DEFAULT_DEFERRAL {
+                        straight.push(case);
+                    }
+                    else if mx == 0 || my == 0 {
+                        shuffle.push(case);
+                    }
+                    else {
+                        slide.push(case);
+                    }
+                }
+            }
+        }
+    }
+    Cases { straight: spread(straight), shuffle: spread(shuffle), slide: spread(slide), fall: spread(fall), masks: spread(masks) }
+}
+
+fn bench_moves(c: &mut Criterion, name: &str, play_map: &PlayMap, kind: &str, cases: &[Case]) {
+    if cases.is_empty() {
+        return;
+    }
+    c.bench_with_input(BenchmarkId::new(kind, name), &cases, |b, cases| {
+        b.iter(|| {
+            for (mx, my, level, rect) in cases.iter() {
+                criterion::black_box(play_map.apply_move(*mx, *my, *level, *rect));
+            }
+        })
+    });
+}
+
+fn map_queries(c: &mut Criterion) {
+    for name in MAPS.iter() {
+        let map_def = load_map_def(name);
+        let play_map = PlayMap::from_data(map_def.to_play_map_data(DEFAULT_TILE_SIZE));
+        let cases = find_cases(&play_map, map_def.rows, map_def.cols);
+        bench_moves(c, name, &play_map, "apply_move/straight", &cases.straight);
+        bench_moves(c, name, &play_map, "apply_move/shuffle", &cases.shuffle);
+        bench_moves(c, name, &play_map, "apply_move/slide", &cases.slide);
+        if !cases.fall.is_empty() {
+            c.bench_with_input(BenchmarkId::new("get_event/fall", name), &cases.fall, |b, cases| {
+                b.iter(|| {
+                    for (level, rect) in cases.iter() {
+                        criterion::black_box(play_map.get_event(*level, *rect));
+                    }
+                })
+            });
+        }
+        if !cases.masks.is_empty() {
+            c.bench_with_input(BenchmarkId::new("get_sprite_masks", name), &cases.masks, |b, cases| {
+                b.iter(|| {
+                    for (level, rect) in cases.iter() {
+                        criterion::black_box(play_map.get_sprite_masks(*rect, z_index(rect, *level), *level, true));
+                    }
+                })
+            });
+        }
+    }
+}
+
+criterion_group!(benches, map_queries);
+criterion_main!(benches);
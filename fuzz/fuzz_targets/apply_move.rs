@@ -0,0 +1,64 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use wasm_ulmo_map::{MapTileData, MovementConfig, PlayMap, PlayMapData, Rect};
+
+#[derive(Arbitrary, Debug)]
+struct Tile {
+    levels: Vec<i8>,
+    down_levels: Vec<(i8, u8)>,
+    special_levels: Vec<i8>
+}
+
+#[derive(Arbitrary, Debug)]
+struct Move {
+    mx: i8,
+    my: i8,
+    level: i8,
+    rect: (i16, i16, u8, u8)
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    rows: u8,
+    cols: u8,
+    tile_size: u8,
+    tiles: Vec<Tile>,
+    moves: Vec<Move>
+}
+
+// Tiles take any levels, including ones no map would have (several specials at once, odd
+// levels everywhere), so this only checks that nothing panics and moves stay in bounds.
+// Landing on a valid span is checked over well formed maps in tests/properties.rs.
+fuzz_target!(|input: Input| {
+    let rows = input.rows % 16 + 1;
+    let cols = input.cols % 16 + 1;
+    let tile_size = input.tile_size as u32 % 64 + 1;
+    let tiles: Vec<MapTileData> = (0..rows as usize * cols as usize)
+        .map(|i| match input.tiles.get(i) {
+            Some(tile) => MapTileData::new(
+                tile.levels.clone(),
+                tile.down_levels.clone(),
+                tile.special_levels.clone(),
+                vec![]
+            ),
+            None => MapTileData::new(vec![], vec![], vec![], vec![])
+        })
+        .collect();
+    let play_map = PlayMap::from_data(PlayMapData::new(rows, cols, tiles, tile_size));
+    let nudge = MovementConfig::new().nudge as i32;
+    for m in input.moves.iter() {
+        // moves are a few pixels a frame, anything bigger would skip over tiles
+        let (mx, my) = (m.mx % 5, m.my % 5);
+        let (x, y, w, h) = m.rect;
+        let rect = Rect::new(x as i32, y as i32, w as u32 + 1, h as u32 + 1);
+        let result = play_map.apply_move(mx, my, m.level, rect);
+        let (dx, dy) = result.deltas();
+        assert!((dx as i32).abs() <= (mx as i32).abs() + nudge);
+        assert!((dy as i32).abs() <= (my as i32).abs() + nudge);
+        play_map.get_event(m.level, rect);
+        play_map.get_sprite_masks(rect, y as i32, m.level, true);
+        play_map.lint_stairs();
+    }
+});
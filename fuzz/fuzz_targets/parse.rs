@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wasm_ulmo_map::{parse_level, parse_mask_level, MapDef, PlayMap, PlayMapData, Rect, Replay};
+
+fuzz_target!(|text: &str| {
+    let _ = parse_level(text);
+    let _ = parse_mask_level(text);
+    if let Ok(replay) = Replay::parse(text) {
+        assert_eq!(Replay::parse(&replay.to_string()), Ok(replay));
+    }
+    if let Ok(play_map_data) = PlayMapData::from_ascii(text, 16) {
+        let play_map = PlayMap::from_data(play_map_data);
+        play_map.to_ascii(Some((&Rect::new(0, 0, 16, 16), 2)));
+        play_map.lint_stairs();
+    }
+    if let Ok(map_def) = serde_json::from_str::<MapDef>(text) {
+        map_def.check(None);
+    }
+});
@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{MapTile, MapTileData, ModifierType, MoveModifier, PlayMap, PlayMapData, Rect, Side};
+
+const SIDES: [(Side, char); 4] = [(Side::TOP, 't'), (Side::RIGHT, 'r'), (Side::BOTTOM, 'b'), (Side::LEFT, 'l')];
+
+// A text grid of tiles, one row per line, in the same (doubled) levels as MapTileData:
+//
+//   [4] [S4] [4]     <- level 4, special level 4
+//   [X] [S3] [X,m4]  <- wall, special level 3, wall with a flat mask at level 4
+//   [2] [D2-2] [2]   <- down level 2 dropping 2
+//
+// Cells are made of comma separated parts: a level, S<level>, D<level>-<drop>, X for a
+// wall, and m<level> or mV<level> for a flat or vertical mask. Masks are on the tile layer
+// given by a trailing @<layer>, eg. m4@2, or on the layer matching their place in the cell.
+// The rest of MapTileData is written as:
+//
+//   I, C<x>:<y>, %<percent>   <- ice, a conveyor and a slow tile
+//   E<level><sides>           <- only entered from the given sides at level, eg. E2tl
+//   F<level><sides>           <- edges that can't be crossed at level, eg. F4r
+//   L<bottom>-<top>           <- a ladder
+//   W<surface>-<depth>        <- water, depth in pixels
+//
+// where sides are any of t, r, b and l. Levels added at runtime (+<level>) and platform
+// levels (P<level>) parse back as plain levels, as that is how the tile treats them.
+// Brackets are optional, as are a leading // and anything after <-, so grids can be
+// pasted straight from comments. Lines starting with @ are ignored.
+impl PlayMapData {
+    pub fn from_ascii(ascii: &str, tile_size: u32) -> Result<PlayMapData, String> {
+        let mut rows: Vec<Vec<MapTileData>> = vec![];
+        for line in ascii.lines() {
+            let line = line.trim().trim_start_matches("//");
+            let line = match line.find("<-") {
+                Some(i) => &line[..i],
+                None => line
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('@') {
+                continue;
+            }
+            let y = rows.len() as u8;
+            let row = line.split_whitespace()
+                .map(|cell| parse_cell(cell, y))
+                .collect::<Result<Vec<MapTileData>, String>>()?;
+            rows.push(row);
+        }
+        let cols = rows.first().map_or(0, |row| row.len());
+        if rows.is_empty() || cols == 0 {
+            return Err("no rows found".to_string());
+        }
+        if rows.len() > u8::MAX as usize || cols > u8::MAX as usize {
+            return Err(format!("{}x{} is too big for a map", cols, rows.len()));
+        }
+        if let Some((y, row)) = rows.iter().enumerate().find(|(_, row)| row.len() != cols) {
+            return Err(format!("row {} has {} cells, expected {}", y, row.len(), cols));
+        }
+        let rows_len = rows.len() as u8;
+        Ok(PlayMapData::new(rows_len, cols as u8, rows.into_iter().flatten().collect(), tile_size))
+    }
+}
+
+fn parse_cell(cell: &str, y: u8) -> Result<MapTileData, String> {
+    let parts = cell.trim_start_matches(['[', '(']).trim_end_matches([']', ')']);
+    let mut levels = vec![];
+    let mut down_levels = vec![];
+    let mut special_levels = vec![];
+    let mut masks = vec![];
+    let mut modifier = None;
+    let mut entry_sides = vec![];
+    let mut edges = vec![];
+    let mut ladder = None;
+    let mut water = None;
+    for part in parts.split(',') {
+        if part == "X" {
+            continue;
+        }
+        if part == "I" {
+            modifier = Some((ModifierType::ICE as u8, 0, 0));
+        }
+        else if let Some(conveyor) = part.strip_prefix('C') {
+            let (x, y) = conveyor.split_once(':')
+                .ok_or_else(|| format!("conveyor needs an x and y: {}", cell))?;
+            modifier = Some((ModifierType::CONVEYOR as u8, parse_number(x, cell)?, parse_number(y, cell)?));
+        }
+        else if let Some(percent) = part.strip_prefix('%') {
+            modifier = Some((ModifierType::SLOW as u8, parse_number(percent, cell)?, 0));
+        }
+        else if let Some(entry) = part.strip_prefix('E') {
+            entry_sides.push(parse_sides(entry, cell)?);
+        }
+        else if let Some(edge) = part.strip_prefix('F') {
+            edges.push(parse_sides(edge, cell)?);
+        }
+        else if let Some(rungs) = part.strip_prefix('L') {
+            let (bottom, top) = split_pair(rungs)
+                .ok_or_else(|| format!("ladder needs a bottom and top: {}", cell))?;
+            ladder = Some((parse_number(bottom, cell)?, parse_number(top, cell)?));
+        }
+        else if let Some(pool) = part.strip_prefix('W') {
+            let (surface, depth) = split_pair(pool)
+                .ok_or_else(|| format!("water needs a depth: {}", cell))?;
+            water = Some((parse_number(surface, cell)?, parse_number(depth, cell)?));
+        }
+        else if let Some(mask) = part.strip_prefix('m') {
+            let (level, flat) = match mask.strip_prefix('V') {
+                Some(level) => (level, false),
+                None => (mask, true)
+            };
+            let (level, layer) = match level.split_once('@') {
+                Some((level, layer)) => (level, parse_number(layer, cell)?),
+                None => (level, masks.len() as u8)
+            };
+            masks.push((layer, parse_number(level, cell)?, flat, y));
+        }
+        else if let Some(special) = part.strip_prefix('S') {
+            special_levels.push(parse_number(special, cell)?);
+        }
+        else if let Some(down) = part.strip_prefix('D') {
+            let (level, drop) = split_pair(down)
+                .ok_or_else(|| format!("down level needs a drop: {}", cell))?;
+            down_levels.push((parse_number(level, cell)?, parse_number(drop, cell)?));
+        }
+        else {
+            levels.push(parse_number(part.trim_start_matches(['+', 'P']), cell)?);
+        }
+    }
+    let mut map_tile_data = MapTileData::new(levels, down_levels, special_levels, masks);
+    map_tile_data.modifier = modifier;
+    map_tile_data.entry_sides = entry_sides;
+    map_tile_data.edges = edges;
+    map_tile_data.ladder = ladder;
+    map_tile_data.water = water;
+    Ok(map_tile_data)
+}
+
+// splits "<a>-<b>" at the first - that isn't a minus sign on a
+fn split_pair(pair: &str) -> Option<(&str, &str)> {
+    let i = pair.char_indices().skip(1).find(|(_, c)| *c == '-')?.0;
+    Some((&pair[..i], &pair[i + 1..]))
+}
+
+// "<level><sides>", eg. "2tl"
+fn parse_sides(part: &str, cell: &str) -> Result<(i8, u8), String> {
+    let i = part.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(part.len());
+    let mut sides = 0;
+    for c in part[i..].chars() {
+        sides |= match SIDES.iter().find(|(_, name)| *name == c) {
+            Some((side, _)) => *side as u8,
+            None => return Err(format!("unknown side {} in cell: {}", c, cell))
+        };
+    }
+    Ok((parse_number(&part[..i], cell)?, sides))
+}
+
+fn render_sides(sides: u8) -> String {
+    SIDES.iter().filter(|(side, _)| sides & *side as u8 != 0).map(|(_, name)| name).collect()
+}
+
+fn parse_number<T: std::str::FromStr>(number: &str, cell: &str) -> Result<T, String> {
+    number.parse().map_err(|_| format!("unknown cell: {}", cell))
+}
+
+impl PlayMap {
+    // Renders the map in the from_ascii format, lined up in columns. Levels added at runtime
+    // show as +<level> and platform levels as P<level>; tiles under the actor (a rect and
+    // level) are wrapped in () rather than [], and the actor is described on a final @ line.
+    // Everything parses back, though added and platform levels come back as plain levels.
+    pub fn to_ascii(&self, actor: Option<(&Rect, i8)>) -> String {
+        let actor_tiles = actor.map(|(rect, _)| self.convert_rect(rect));
+        let mut cells = vec![];
+        for y in 0..self.rows {
+            for x in 0..self.cols {
+                // a short tile list renders as walls, which is how the missing tiles behave
+                let cell = self.tiles.get(self.get_index(x, y)).map_or_else(|| "X".to_string(), render_cell);
+                let under_actor = actor_tiles.is_some_and(|(tx1, ty1, tx2, ty2)| {
+                    x >= tx1 && x < tx2 && y >= ty1 && y < ty2
+                });
+                cells.push(if under_actor { format!("({})", cell) } else { format!("[{}]", cell) });
+            }
+        }
+        let mut widths = vec![0; self.cols as usize];
+        for (i, cell) in cells.iter().enumerate() {
+            let x = i % self.cols as usize;
+            widths[x] = widths[x].max(cell.len());
+        }
+        let mut ascii = String::new();
+        for row in cells.chunks(self.cols as usize) {
+            let line: Vec<String> = row.iter().enumerate()
+                .map(|(x, cell)| format!("{:width$}", cell, width = widths[x]))
+                .collect();
+            ascii.push_str(line.join(" ").trim_end());
+            ascii.push('\n');
+        }
+        if let Some((rect, level)) = actor {
+            let (left, top) = rect.top_left();
+            ascii.push_str(&format!("@ level {} at ({}, {}) {}x{}\n", level, left, top, rect.width(), rect.height()));
+        }
+        ascii
+    }
+}
+
+fn render_cell(tile: &MapTile) -> String {
+    let mut parts = vec![];
+    for level in tile.levels.iter() {
+        let added = !tile.filled_levels.contains(level)
+            && tile.old_levels.as_ref().is_some_and(|old_levels| !old_levels.contains(level));
+        parts.push(if added { format!("+{}", level) } else { level.to_string() });
+    }
+    let special_levels: BTreeSet<&i8> = tile.special_levels.values().collect();
+    for level in special_levels {
+        parts.push(format!("S{}", level));
+    }
+    let down_levels: BTreeSet<(&i8, &u8)> = tile.down_levels.iter().collect();
+    for (level, drop) in down_levels {
+        parts.push(format!("D{}-{}", level, drop));
+    }
+    for level in tile.platform_levels.iter() {
+        parts.push(format!("P{}", level));
+    }
+    match tile.modifier {
+        None => (),
+        Some(MoveModifier::Ice) => parts.push("I".to_string()),
+        Some(MoveModifier::Conveyor(x, y)) => parts.push(format!("C{}:{}", x, y)),
+        Some(MoveModifier::Slow(percent)) => parts.push(format!("%{}", percent))
+    }
+    let entry_sides: BTreeMap<&i8, &u8> = tile.entry_sides.iter().collect();
+    for (level, sides) in entry_sides {
+        parts.push(format!("E{}{}", level, render_sides(*sides)));
+    }
+    let edges: BTreeMap<&i8, &u8> = tile.edges.iter().collect();
+    for (level, sides) in edges {
+        parts.push(format!("F{}{}", level, render_sides(*sides)));
+    }
+    if let Some((bottom, top)) = tile.ladder {
+        parts.push(format!("L{}-{}", bottom, top));
+    }
+    if let Some((surface, depth)) = tile.water {
+        parts.push(format!("W{}-{}", surface, depth));
+    }
+    if parts.is_empty() {
+        parts.push("X".to_string());
+    }
+    if let Some(mask_infos) = &tile.masks {
+        for (i, mask_info) in mask_infos.iter().enumerate() {
+            let mut part = if mask_info.flat { format!("m{}", mask_info.level) } else { format!("mV{}", mask_info.level) };
+            if mask_info.tile_index as usize != i {
+                part.push_str(&format!("@{}", mask_info.tile_index));
+            }
+            parts.push(part);
+        }
+    }
+    parts.join(",")
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use wasm_ulmo_map::{MapDef, PlayMap, Replay, TileSetDef, DEFAULT_TILE_SIZE};
+
+const USAGE: &str = "usage: ulmo-map check [--tilesets DIR] FILE...\n       ulmo-map replay MAP REPLAY";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|s| s.as_str()) {
+        Some("check") => check(&args[1..]),
+        Some("replay") if args.len() == 3 => replay(&args[1], &args[2]),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+fn check(args: &[String]) {
+    let mut tile_sets_dir = None;
+    let mut files = vec![];
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if arg == "--tilesets" {
+            match rest.next() {
+                Some(dir) => tile_sets_dir = Some(PathBuf::from(dir)),
+                None => {
+                    eprintln!("{}", USAGE);
+                    process::exit(2);
+                }
+            }
+        }
+        else {
+            files.push(PathBuf::from(arg));
+        }
+    }
+    if files.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    for file in files.iter() {
+        // tilesets live alongside the maps unless told otherwise
+        let dir = tile_sets_dir.clone().or_else(|| default_tile_sets_dir(file));
+        let tile_sets = match dir.map(|dir| load_tile_sets(&dir)).transpose() {
+            Ok(tile_sets) => tile_sets,
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(2);
+            }
+        };
+        match check_file(file, tile_sets.as_ref()) {
+            Ok(problems) if problems.is_empty() => println!("{}: ok", file.display()),
+            Ok(problems) => {
+                failed = true;
+                for problem in problems {
+                    println!("{}: {}", file.display(), problem);
+                }
+            }
+            Err(message) => {
+                failed = true;
+                println!("{}: {}", file.display(), message);
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+// re-runs a recording from the game against a map file, exiting 1 at the first difference
+fn replay(map_file: &str, replay_file: &str) {
+    let result = fs::read_to_string(map_file)
+        .map_err(|e| format!("{}: {}", map_file, e))
+        .and_then(|json| serde_json::from_str::<MapDef>(&json).map_err(|e| format!("{}: {}", map_file, e)))
+        .and_then(|map_def| {
+            let text = fs::read_to_string(replay_file).map_err(|e| format!("{}: {}", replay_file, e))?;
+            let replay = Replay::parse(&text).map_err(|e| format!("{}: {}", replay_file, e))?;
+            Ok((map_def, replay))
+        });
+    let (map_def, replay) = match result {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+    let tile_size = replay.tile_size().unwrap_or(DEFAULT_TILE_SIZE);
+    let mut play_map = PlayMap::from_data(map_def.to_play_map_data(tile_size));
+    match replay.run(&mut play_map) {
+        Ok(()) => println!("{}: ok ({} calls)", replay_file, replay.calls().len()),
+        Err(mismatch) => {
+            println!("{}: {}", replay_file, mismatch);
+            process::exit(1);
+        }
+    }
+}
+
+fn check_file(file: &Path, tile_sets: Option<&HashMap<String, TileSetDef>>) -> Result<Vec<String>, String> {
+    let json = fs::read_to_string(file).map_err(|e| e.to_string())?;
+    let map_def: MapDef = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    Ok(map_def.check(tile_sets).iter().map(|problem| problem.to_string()).collect())
+}
+
+fn default_tile_sets_dir(file: &Path) -> Option<PathBuf> {
+    let dir = file.parent()?.join("../tilesets");
+    if dir.is_dir() {
+        Some(dir)
+    }
+    else {
+        None
+    }
+}
+
+fn load_tile_sets(dir: &Path) -> Result<HashMap<String, TileSetDef>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut tile_sets = HashMap::new();
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let json = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let tile_set: TileSetDef = serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))?;
+        tile_sets.insert(tile_set.name.clone(), tile_set);
+    }
+    Ok(tile_sets)
+}
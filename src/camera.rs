@@ -0,0 +1,166 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use std::cmp::min;
+
+use crate::{Rect, TileRange};
+
+// Camera that follows a target rect around a map of known pixel bounds. The
+// camera position is tracked as a (fractional) center point so that smoothing
+// can ease towards the target over several ticks.
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = WasmCamera))]
+pub struct Camera {
+    view_width: u32,
+    view_height: u32,
+    map_width: u32,
+    map_height: u32,
+    dead_zone_width: u32,
+    dead_zone_height: u32,
+    smoothing: f32,
+    look_ahead: i32,
+    cx: f32,
+    cy: f32,
+    shake_magnitude: u32,
+    shake_ticks: u32,
+    shake_seed: u32
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_class = WasmCamera))]
+impl Camera {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(view_width: u32, view_height: u32, map_width: u32, map_height: u32) -> Camera {
+        Camera {
+            view_width,
+            view_height,
+            map_width,
+            map_height,
+            dead_zone_width: 0,
+            dead_zone_height: 0,
+            smoothing: 1.0,
+            look_ahead: 0,
+            cx: view_width as f32 / 2.0,
+            cy: view_height as f32 / 2.0,
+            shake_magnitude: 0,
+            shake_ticks: 0,
+            shake_seed: 0x2545_f491
+        }
+    }
+
+    // target can move freely within a centered box of this size before the camera follows
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = setDeadZone))]
+    pub fn set_dead_zone(&mut self, width: u32, height: u32) {
+        self.dead_zone_width = min(width, self.view_width);
+        self.dead_zone_height = min(height, self.view_height);
+    }
+
+    // fraction of the remaining distance covered per tick: 1.0 snaps, smaller values ease
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = setSmoothing))]
+    pub fn set_smoothing(&mut self, smoothing: f32) {
+        self.smoothing = smoothing.clamp(0.0, 1.0);
+    }
+
+    // distance in pixels the camera looks ahead in the direction of movement
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = setLookAhead))]
+    pub fn set_look_ahead(&mut self, look_ahead: i32) {
+        self.look_ahead = look_ahead;
+    }
+
+    pub fn shake(&mut self, magnitude: u32, ticks: u32) {
+        self.shake_magnitude = min(magnitude, i8::MAX as u32);
+        self.shake_ticks = ticks;
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = centerOn))]
+    pub fn center_on(&mut self, target: Rect) -> Rect {
+        let (fx, fy) = Camera::center_of(&target);
+        self.cx = fx;
+        self.cy = fy;
+        self.clamp_center();
+        self.view_rect()
+    }
+
+    pub fn follow(&mut self, target: Rect, mx: i8, my: i8) -> Rect {
+        let (fx, fy) = Camera::center_of(&target);
+        let fx = fx + (mx.signum() as i32 * self.look_ahead) as f32;
+        let fy = fy + (my.signum() as i32 * self.look_ahead) as f32;
+        let desired_x = Camera::dead_zone_target(self.cx, fx, self.dead_zone_width);
+        let desired_y = Camera::dead_zone_target(self.cy, fy, self.dead_zone_height);
+        self.cx += (desired_x - self.cx) * self.smoothing;
+        self.cy += (desired_y - self.cy) * self.smoothing;
+        self.clamp_center();
+        let view_rect = self.view_rect();
+        if self.shake_ticks > 0 {
+            self.shake_ticks -= 1;
+            let (sx, sy) = self.next_shake_offset();
+            return view_rect.move_rect(sx, sy);
+        }
+        view_rect
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = viewRect))]
+    pub fn view_rect(&self) -> Rect {
+        let left = self.cx.round() as i32 - (self.view_width / 2) as i32;
+        let top = self.cy.round() as i32 - (self.view_height / 2) as i32;
+        Rect::new(left, top, self.view_width, self.view_height)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = visibleTiles))]
+    pub fn visible_tiles(&self, tile_size: u32) -> TileRange {
+        if tile_size == 0 {
+            return TileRange::new(0, 0, 0, 0);
+        }
+        let cols = min(self.map_width.div_ceil(tile_size), u8::MAX as u32) as u8;
+        let rows = min(self.map_height.div_ceil(tile_size), u8::MAX as u32) as u8;
+        self.view_rect().to_tile_range(tile_size, cols, rows)
+    }
+}
+
+impl Camera {
+    fn center_of(rect: &Rect) -> (f32, f32) {
+        (
+            rect.left as f32 + rect.width as f32 / 2.0,
+            rect.top as f32 + rect.height as f32 / 2.0
+        )
+    }
+
+    fn dead_zone_target(center: f32, focus: f32, dead_zone: u32) -> f32 {
+        let half = dead_zone as f32 / 2.0;
+        if focus < center - half {
+            focus + half
+        }
+        else if focus > center + half {
+            focus - half
+        }
+        else {
+            center
+        }
+    }
+
+    fn clamp_axis(center: f32, view: u32, map: u32) -> f32 {
+        let half = view as f32 / 2.0;
+        if map < view {
+            // needed to center maps smaller than the view
+            return map as f32 / 2.0;
+        }
+        center.max(half).min(map as f32 - half)
+    }
+
+    fn clamp_center(&mut self) {
+        self.cx = Camera::clamp_axis(self.cx, self.view_width, self.map_width);
+        self.cy = Camera::clamp_axis(self.cy, self.view_height, self.map_height);
+    }
+
+    // xorshift keeps the shake deterministic so replays look the same
+    fn next_shake_offset(&mut self) -> (i8, i8) {
+        let range = self.shake_magnitude as i32 * 2 + 1;
+        let mut next = || {
+            self.shake_seed ^= self.shake_seed << 13;
+            self.shake_seed ^= self.shake_seed >> 17;
+            self.shake_seed ^= self.shake_seed << 5;
+            (self.shake_seed % range as u32) as i32 - self.shake_magnitude as i32
+        };
+        let sx = next();
+        let sy = next();
+        (sx as i8, sy as i8)
+    }
+}
@@ -0,0 +1,179 @@
+use serde::Serialize;
+
+use crate::MoveResult;
+
+// why the primary move was rejected
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Rejection {
+    // the tiles under the new rect aren't valid at the level
+    Tiles,
+    // a solid sprite is in the way
+    Sprite,
+    // a one-way tile or an edge is in the way
+    Crossing,
+    // climbers can only move up and down the ladder
+    Ladder
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Fallback {
+    Shuffle,
+    Slide
+}
+
+// why a fallback didn't help
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum FallbackFailure {
+    // assistance is turned off in the movement config
+    Disabled,
+    // the rect only spans one stripe of tiles, so there's nowhere to shuffle to
+    SingleStripe,
+    // the rect overlaps the blocked stripe by more than the config allows
+    Overlap,
+    // every option was blocked by tiles, sprites or crossings
+    Blocked
+}
+
+// a tile under the new rect and what get_validity_of made of it
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TileCheck {
+    tx: u8,
+    ty: u8,
+    level: i8,
+    inc: u8,
+    special_level: Option<i8>
+}
+
+impl TileCheck {
+    pub fn new(tx: u8, ty: u8, level: i8, validity: (u8, Option<i8>)) -> TileCheck {
+        let (inc, special_level) = validity;
+        TileCheck { tx, ty, level, inc, special_level }
+    }
+
+    pub fn is_blocking(&self) -> bool {
+        self.inc == 0
+    }
+
+    pub fn as_tuple(&self) -> (u8, u8, i8, u8, Option<i8>) {
+        (self.tx, self.ty, self.level, self.inc, self.special_level)
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FallbackAttempt {
+    fallback: Fallback,
+    failure: Option<FallbackFailure>,
+    // the move that was tried, eg. one axis of a slide
+    step: Option<(i8, i8)>,
+    // why that move was rejected, and the first tile that couldn't be stood on if it was the tiles
+    rejection: Option<Rejection>,
+    blocking_tile: Option<(u8, u8)>
+}
+
+impl FallbackAttempt {
+    pub fn new(fallback: Fallback, failure: Option<FallbackFailure>) -> FallbackAttempt {
+        FallbackAttempt { fallback, failure, step: None, rejection: None, blocking_tile: None }
+    }
+
+    pub fn with_step(mut self, mx: i8, my: i8) -> FallbackAttempt {
+        self.step = Some((mx, my));
+        self
+    }
+
+    pub fn with_rejection(mut self, rejection: Option<Rejection>, blocking_tile: Option<(u8, u8)>) -> FallbackAttempt {
+        self.rejection = rejection;
+        self.blocking_tile = blocking_tile;
+        self
+    }
+
+    pub fn step(&self) -> Option<(i8, i8)> {
+        self.step
+    }
+
+    pub fn rejection(&self) -> Option<Rejection> {
+        self.rejection
+    }
+
+    pub fn blocking_tile(&self) -> Option<(u8, u8)> {
+        self.blocking_tile
+    }
+
+    pub fn as_tuple(&self) -> (Fallback, Option<FallbackFailure>) {
+        (self.fallback, self.failure)
+    }
+}
+
+// What apply_move_for did, filled in as it goes when a move is being explained, so
+// that the diagnostics can't drift from the rules actually applied.
+#[derive(Debug, Default)]
+pub struct MoveTrace {
+    rejection: Option<Rejection>,
+    tiles: Vec<TileCheck>,
+    fallbacks: Vec<FallbackAttempt>
+}
+
+impl MoveTrace {
+    pub fn new() -> MoveTrace {
+        MoveTrace::default()
+    }
+
+    pub fn check(&mut self, tiles: Vec<TileCheck>, rejection: Option<Rejection>) {
+        self.tiles = tiles;
+        self.rejection = rejection;
+    }
+
+    pub fn attempt(&mut self, attempt: FallbackAttempt) {
+        self.fallbacks.push(attempt);
+    }
+}
+
+// Everything apply_move looked at while deciding on a move, for working out why
+// a sprite got stuck. Only the tiles that returned no validity are blocking.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveDiagnostics {
+    valid: bool,
+    deferral: u8,
+    level: i8,
+    mx: i8,
+    my: i8,
+    rejection: Option<Rejection>,
+    tiles: Vec<TileCheck>,
+    fallbacks: Vec<FallbackAttempt>
+}
+
+impl MoveDiagnostics {
+    pub fn new(result: &MoveResult, rejection: Option<Rejection>, tiles: Vec<TileCheck>, fallbacks: Vec<FallbackAttempt>) -> MoveDiagnostics {
+        let (valid, deferral, level, mx, my) = result.as_tuple();
+        MoveDiagnostics { valid, deferral, level, mx, my, rejection, tiles, fallbacks }
+    }
+
+    pub fn from_trace(result: &MoveResult, trace: MoveTrace) -> MoveDiagnostics {
+        MoveDiagnostics::new(result, trace.rejection, trace.tiles, trace.fallbacks)
+    }
+
+    pub fn result_tuple(&self) -> (bool, u8, i8, i8, i8) {
+        (self.valid, self.deferral, self.level, self.mx, self.my)
+    }
+
+    pub fn rejection(&self) -> Option<Rejection> {
+        self.rejection
+    }
+
+    pub fn tiles(&self) -> &[TileCheck] {
+        &self.tiles
+    }
+
+    pub fn blocking_tiles(&self) -> Vec<&TileCheck> {
+        self.tiles.iter().filter(|tile| tile.is_blocking()).collect()
+    }
+
+    pub fn fallbacks(&self) -> &[FallbackAttempt] {
+        &self.fallbacks
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use crate::{Capabilities, MovementConfig, Rect};
+
+// fixed-point positions are in 1/256ths of a pixel
+pub const SUBPIXELS: i32 = 256;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entity {
+    rect: Rect,
+    level: i8,
+    solid: bool,
+    pushable: bool,
+    momentum: (i8, i8),
+    remainder: (i32, i32),
+    config: MovementConfig,
+    capabilities: Capabilities
+}
+
+impl Entity {
+    pub fn new(rect: Rect, level: i8, solid: bool, pushable: bool) -> Entity {
+        Entity {
+            rect,
+            level,
+            solid,
+            pushable,
+            momentum: (0, 0),
+            remainder: (0, 0),
+            config: MovementConfig::default(),
+            capabilities: Capabilities::default()
+        }
+    }
+
+    pub fn sprite(rect: Rect, level: i8) -> Entity {
+        Entity::new(rect, level, false, false)
+    }
+
+    pub fn obstacle(rect: Rect, level: i8) -> Entity {
+        Entity::new(rect, level, true, false)
+    }
+
+    // pushable entities are also solid, so movers push them rather than walk through them
+    pub fn pushable(rect: Rect, level: i8) -> Entity {
+        Entity::new(rect, level, true, true)
+    }
+
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    pub fn level(&self) -> i8 {
+        self.level
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.solid
+    }
+
+    pub fn is_pushable(&self) -> bool {
+        self.pushable
+    }
+
+    pub fn momentum(&self) -> (i8, i8) {
+        self.momentum
+    }
+
+    pub fn config(&self) -> MovementConfig {
+        self.config
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    // the top left of the rect in subpixels, including movement that hasn't added up to a pixel yet
+    pub fn fixed_position(&self) -> (i32, i32) {
+        let (left, top) = self.rect.top_left();
+        (left * SUBPIXELS + self.remainder.0, top * SUBPIXELS + self.remainder.1)
+    }
+}
+
+// Sprites registered by id, bucketed into the tiles their base rects span so
+// that overlap queries only need to look at the entities in nearby tiles.
+#[derive(Debug)]
+pub struct EntityRegistry {
+    cols: u8,
+    rows: u8,
+    tile_size: u32,
+    entities: HashMap<u32, Entity>,
+    cells: Vec<Vec<u32>>
+}
+
+impl EntityRegistry {
+    pub fn new(rows: u8, cols: u8, tile_size: u32) -> EntityRegistry {
+        EntityRegistry {
+            cols,
+            rows,
+            tile_size,
+            entities: HashMap::new(),
+            cells: vec![vec![]; rows as usize * cols as usize]
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Entity> {
+        self.entities.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn insert(&mut self, id: u32, entity: Entity) {
+        self.remove(id);
+        self.add_to_cells(id, &entity.rect);
+        self.entities.insert(id, entity);
+    }
+
+    pub fn move_to(&mut self, id: u32, rect: Rect, level: i8) -> bool {
+        match self.entities.get(&id) {
+            None => false,
+            Some(entity) => {
+                let old_rect = entity.rect;
+                let entity = Entity { rect, level, ..*entity };
+                self.remove_from_cells(id, &old_rect);
+                self.add_to_cells(id, &rect);
+                self.entities.insert(id, entity);
+                true
+            }
+        }
+    }
+
+    pub fn set_solid(&mut self, id: u32, solid: bool) -> bool {
+        match self.entities.get_mut(&id) {
+            None => false,
+            Some(entity) => {
+                entity.solid = solid;
+                true
+            }
+        }
+    }
+
+    pub fn set_config(&mut self, id: u32, config: MovementConfig) -> bool {
+        match self.entities.get_mut(&id) {
+            None => false,
+            Some(entity) => {
+                entity.config = config;
+                true
+            }
+        }
+    }
+
+    pub fn set_capabilities(&mut self, id: u32, capabilities: Capabilities) -> bool {
+        match self.entities.get_mut(&id) {
+            None => false,
+            Some(entity) => {
+                entity.capabilities = capabilities;
+                true
+            }
+        }
+    }
+
+    pub fn set_momentum(&mut self, id: u32, momentum: (i8, i8)) -> bool {
+        match self.entities.get_mut(&id) {
+            None => false,
+            Some(entity) => {
+                entity.momentum = momentum;
+                true
+            }
+        }
+    }
+
+    // adds subpixel movement to the entity's remainder and takes out the whole pixels to move by
+    pub fn accumulate(&mut self, id: u32, fmx: i32, fmy: i32) -> Option<(i8, i8)> {
+        let entity = self.entities.get_mut(&id)?;
+        let (mx, rx) = EntityRegistry::whole_pixels(entity.remainder.0.saturating_add(fmx));
+        let (my, ry) = EntityRegistry::whole_pixels(entity.remainder.1.saturating_add(fmy));
+        entity.remainder = (rx, ry);
+        Some((mx, my))
+    }
+
+    pub fn clear_remainder(&mut self, id: u32) -> bool {
+        match self.entities.get_mut(&id) {
+            None => false,
+            Some(entity) => {
+                entity.remainder = (0, 0);
+                true
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        match self.entities.remove(&id) {
+            None => false,
+            Some(entity) => {
+                self.remove_from_cells(id, &entity.rect);
+                true
+            }
+        }
+    }
+
+    // ids of entities at the given level whose rects overlap rect, in ascending order
+    pub fn overlapping(&self, rect: &Rect, level: i8) -> Vec<u32> {
+        let mut ids = self.candidates(rect);
+        ids.retain(|id| {
+            let entity = &self.entities[id];
+            entity.level == level && entity.rect.intersects_with(rect)
+        });
+        ids
+    }
+
+    // true if a solid entity (other than ignore) at the given level overlaps rect
+    pub fn is_blocked(&self, rect: &Rect, level: i8, ignore: Option<u32>) -> bool {
+        self.cell_indices(rect)
+            .flat_map(|index| self.cells[index].iter())
+            .filter(|id| Some(**id) != ignore)
+            .any(|id| {
+                let entity = &self.entities[id];
+                entity.solid && entity.level == level && entity.rect.intersects_with(rect)
+            })
+    }
+
+    // ids of other entities that the given entity would bump into if moved by (mx, my)
+    pub fn collisions(&self, id: u32, mx: i8, my: i8) -> Vec<u32> {
+        match self.entities.get(&id) {
+            None => vec![],
+            Some(entity) => {
+                let mut ids = self.overlapping(&entity.rect.move_rect(mx, my), entity.level);
+                ids.retain(|other| *other != id);
+                ids
+            }
+        }
+    }
+
+    // rounds towards zero so that movement is the same in both directions; anything
+    // beyond an i8 of pixels is dropped
+    fn whole_pixels(fixed: i32) -> (i8, i32) {
+        let pixels = (fixed / SUBPIXELS).clamp(i8::MIN as i32, i8::MAX as i32);
+        (pixels as i8, fixed % SUBPIXELS)
+    }
+
+    fn candidates(&self, rect: &Rect) -> Vec<u32> {
+        let mut ids = vec![];
+        for index in self.cell_indices(rect) {
+            ids.extend_from_slice(&self.cells[index]);
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    fn cell_indices(&self, rect: &Rect) -> impl Iterator<Item = usize> {
+        let (tx1, ty1, tx2, ty2) = rect.to_tile_range(self.tile_size, self.cols, self.rows).as_tuple();
+        let cols = self.cols as usize;
+        (ty1..ty2).flat_map(move |y| (tx1..tx2).map(move |x| y as usize * cols + x as usize))
+    }
+
+    fn add_to_cells(&mut self, id: u32, rect: &Rect) {
+        for index in self.cell_indices(rect) {
+            self.cells[index].push(id);
+        }
+    }
+
+    fn remove_from_cells(&mut self, id: u32, rect: &Rect) {
+        for index in self.cell_indices(rect) {
+            self.cells[index].retain(|other| *other != id);
+        }
+    }
+}
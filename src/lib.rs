@@ -1,19 +1,46 @@
+#[cfg(feature = "wasm")]
 mod utils;
-
+mod camera;
+mod entities;
+mod platforms;
+mod movement;
+mod diagnostics;
+mod maps;
+mod stairs;
+mod ascii;
+mod replay;
+mod simulation;
+
+pub use camera::Camera;
+pub use entities::{Entity, EntityRegistry, SUBPIXELS};
+pub use platforms::{Platform, Waypoint};
+pub use movement::{MovementConfig, Capabilities};
+pub use diagnostics::{MoveDiagnostics, MoveTrace, TileCheck, FallbackAttempt, Rejection, Fallback, FallbackFailure};
+pub use stairs::{BrokenStair, StairEnd, StairFault};
+pub use replay::{Replay, ReplayCall, ReplayMismatch};
+pub use simulation::{Simulation, Actor, Frame, Key};
+pub use maps::{DEFAULT_TILE_SIZE, MapDef, MapTileDef, TileDef, TileSetDef, TileSetTileDef, Level, MapProblem, parse_level, parse_mask_level};
+
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+use std::cell::RefCell;
 use std::collections::{HashMap, BTreeMap};
 
+#[cfg(feature = "wasm")]
 extern crate web_sys;
+#[cfg(feature = "wasm")]
 use web_sys::console;
-use wasm_bindgen::__rt::core::cmp::{max, min};
+use std::cmp::{max, min};
 
 // #[macro_use]
 extern crate serde;
+#[cfg(feature = "wasm")]
 extern crate serde_wasm_bindgen;
 use serde::{Serialize, Deserialize};
 
 // A macro to provide `println!(..)`-style syntax for `console.log` logging.
+#[cfg(feature = "wasm")]
 macro_rules! log {
     ( $( $t:tt )* ) => {
         console::log_1(&format!( $( $t )* ).into());
@@ -25,11 +52,14 @@ macro_rules! log {
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
+// how far above the take off level a jump can clear (ie. one level)
+const JUMP_CLEARANCE: i8 = 2;
+
 fn is_odd(number: &i8) -> bool {
     number & 1 == 1
 }
 
-#[wasm_bindgen(js_name = WasmRect)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = WasmRect))]
 #[derive(Debug, Clone, Copy)]
 pub struct Rect {
     left: i32,
@@ -40,15 +70,129 @@ pub struct Rect {
     height: u32
 }
 
-#[wasm_bindgen(js_class = WasmRect)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_class = WasmRect))]
 impl Rect {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(x: i32, y: i32, width: u32, height: u32) -> Rect {
         Rect { left: x, top: y, right: x + width as i32, bottom: y + height as i32, width, height }
     }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn left(&self) -> i32 {
+        self.left
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn top(&self) -> i32 {
+        self.top
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn right(&self) -> i32 {
+        self.right
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn bottom(&self) -> i32 {
+        self.bottom
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = centerX))]
+    pub fn center_x(&self) -> i32 {
+        self.left + (self.width / 2) as i32
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter = centerY))]
+    pub fn center_y(&self) -> i32 {
+        self.top + (self.height / 2) as i32
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = move))]
+    pub fn offset(&self, dx: i32, dy: i32) -> Rect {
+        Rect::new(self.left + dx, self.top + dy, self.width, self.height)
+    }
+
+    // edges are half-open, so rects that only touch do not intersect
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = intersectsWith))]
+    pub fn intersects_with(&self, other: &Rect) -> bool {
+        other.left < self.right && other.top < self.bottom &&
+            other.right > self.left && other.bottom > self.top
+    }
+
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        if !self.intersects_with(other) {
+            return None;
+        }
+        Some(Rect::from_edges(
+            max(self.left, other.left),
+            max(self.top, other.top),
+            min(self.right, other.right),
+            min(self.bottom, other.bottom)
+        ))
+    }
+
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect::from_edges(
+            min(self.left, other.left),
+            min(self.top, other.top),
+            max(self.right, other.right),
+            max(self.bottom, other.bottom)
+        )
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = containsPoint))]
+    pub fn contains_point(&self, x: i32, y: i32) -> bool {
+        x >= self.left && x < self.right && y >= self.top && y < self.bottom
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = containsRect))]
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        other.left >= self.left && other.right <= self.right &&
+            other.top >= self.top && other.bottom <= self.bottom
+    }
+
+    // grows the rect by dx on the left and right, and by dy on the top and bottom
+    pub fn inflate(&self, dx: i32, dy: i32) -> Rect {
+        Rect::from_edges(self.left - dx, self.top - dy, self.right + dx, self.bottom + dy)
+    }
+
+    pub fn deflate(&self, dx: i32, dy: i32) -> Rect {
+        self.inflate(-dx, -dy)
+    }
+
+    // the tiles spanned by this rect, clipped to a map of the given size
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = toTileRange))]
+    pub fn to_tile_range(&self, tile_size: u32, cols: u8, rows: u8) -> TileRange {
+        if tile_size == 0 || tile_size > i32::MAX as u32 {
+            return TileRange::new(0, 0, 0, 0);
+        }
+        let ts = tile_size as i32;
+        let clip = |t: i32, limit: u8| max(0, min(limit as i32, t)) as u8;
+        TileRange::new(
+            clip(self.left.div_euclid(ts), cols),
+            clip(self.top.div_euclid(ts), rows),
+            clip((self.right - 1).div_euclid(ts) + 1, cols),
+            clip((self.bottom - 1).div_euclid(ts) + 1, rows)
+        )
+    }
 }
 
 impl Rect {
+    // collapses to zero width/height rather than inverting
+    pub fn from_edges(left: i32, top: i32, right: i32, bottom: i32) -> Rect {
+        Rect::new(left, top, max(0, right - left) as u32, max(0, bottom - top) as u32)
+    }
+
     pub fn top_left(&self) -> (i32, i32) {
         (self.left, self.top)
     }
@@ -74,6 +218,37 @@ impl Rect {
         let (left, top) = other_rect.top_left();
         ((left - self.left) as i8, (top - self.top) as i8)
     }
+
+    pub fn center(&self) -> (i32, i32) {
+        (self.center_x(), self.center_y())
+    }
+}
+
+// half-open range of tile coordinates: x in tx1..tx2, y in ty1..ty2
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileRange {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub tx1: u8,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub ty1: u8,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub tx2: u8,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub ty2: u8
+}
+
+impl TileRange {
+    pub fn new(tx1: u8, ty1: u8, tx2: u8, ty2: u8) -> TileRange {
+        TileRange { tx1, ty1, tx2, ty2 }
+    }
+
+    pub fn as_tuple(&self) -> (u8, u8, u8, u8) {
+        (self.tx1, self.ty1, self.tx2, self.ty2)
+    }
 }
 
 #[derive(Deserialize)]
@@ -82,7 +257,21 @@ pub struct  MapTileData {
     levels: Vec<i8>,
     down_levels: Vec<(i8, u8)>,
     special_levels: Vec<i8>,
-    masks: Vec<(u8, i8, bool, u8)>
+    masks: Vec<(u8, i8, bool, u8)>,
+    #[serde(default)]
+    modifier: Option<(u8, i8, i8)>,
+    // (level, sides) - at this level the tile can only be entered from these sides
+    #[serde(default)]
+    entry_sides: Vec<(i8, u8)>,
+    // (level, sides) - at this level these sides of the tile can't be crossed in either direction
+    #[serde(default)]
+    edges: Vec<(i8, u8)>,
+    // (bottom, top) - levels joined by a ladder running up this tile
+    #[serde(default)]
+    ladder: Option<(i8, i8)>,
+    // (surface level, depth in pixels)
+    #[serde(default)]
+    water: Option<(i8, u8)>
 }
 
 impl MapTileData {
@@ -92,7 +281,17 @@ impl MapTileData {
         special_levels: Vec<i8>,
         masks: Vec<(u8, i8, bool, u8)>
     ) -> MapTileData {
-        MapTileData { levels, down_levels, special_levels, masks }
+        MapTileData {
+            levels,
+            down_levels,
+            special_levels,
+            masks,
+            modifier: None,
+            entry_sides: vec![],
+            edges: vec![],
+            ladder: None,
+            water: None
+        }
     }
 
     pub fn empty() -> MapTileData {
@@ -114,6 +313,79 @@ impl MapTileData {
     pub fn with_masks(masks: Vec<(u8, i8, bool, u8)>) -> MapTileData {
         Self::new(vec![], vec![], vec![], masks)
     }
+
+    // see MoveModifier::from_data for the (kind, x, y) encoding
+    pub fn with_modifier(mut self, modifier: (u8, i8, i8)) -> MapTileData {
+        self.modifier = Some(modifier);
+        self
+    }
+
+    // sides are a combination of the Side flags
+    pub fn with_entry_sides(mut self, level: i8, sides: u8) -> MapTileData {
+        self.entry_sides.push((level, sides));
+        self
+    }
+
+    pub fn with_edges(mut self, level: i8, sides: u8) -> MapTileData {
+        self.edges.push((level, sides));
+        self
+    }
+
+    pub fn with_ladder(mut self, bottom: i8, top: i8) -> MapTileData {
+        self.ladder = Some((bottom, top));
+        self
+    }
+
+    pub fn with_water(mut self, surface: i8, depth: u8) -> MapTileData {
+        self.water = Some((surface, depth));
+        self
+    }
+}
+
+// tile sides as bit flags
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy)]
+enum Side {
+    TOP = 1,
+    RIGHT = 2,
+    BOTTOM = 4,
+    LEFT = 8,
+}
+
+// enum with explicit discriminator
+#[allow(clippy::upper_case_acronyms)]
+enum ModifierType {
+    ICE = 1,
+    CONVEYOR = 2,
+    SLOW = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveModifier {
+    // momentum carries the mover on until it hits something
+    Ice,
+    // mover is pushed by (x, y) every move
+    Conveyor(i8, i8),
+    // mover only moves the given percentage of the requested distance
+    Slow(u8)
+}
+
+impl MoveModifier {
+    pub fn from_data(modifier_data: (u8, i8, i8)) -> Option<MoveModifier> {
+        let (kind, x, y) = modifier_data;
+        match kind {
+            k if k == ModifierType::ICE as u8 => Some(MoveModifier::Ice),
+            k if k == ModifierType::CONVEYOR as u8 => Some(MoveModifier::Conveyor(x, y)),
+            k if k == ModifierType::SLOW as u8 && (0..=100).contains(&x) => Some(MoveModifier::Slow(x as u8)),
+            _ => None
+        }
+    }
+
+    // slowed movement rounds away from zero so that a slow tile never stops the mover
+    fn slow(m: i8, percent: u8) -> i8 {
+        let scaled = m as i32 * percent as i32;
+        (scaled.signum() * ((scaled.abs() + 99) / 100)) as i8
+    }
 }
 
 #[derive(Debug)]
@@ -160,7 +432,14 @@ pub struct MapTile {
     down_levels: HashMap<i8, u8>,
     special_levels: BTreeMap<i8, i8>,
     masks: Option<Vec<MaskInfo>>,
-    old_levels: Option<Vec<i8>>
+    old_levels: Option<Vec<i8>>,
+    filled_levels: Vec<i8>,
+    platform_levels: Vec<i8>,
+    modifier: Option<MoveModifier>,
+    entry_sides: HashMap<i8, u8>,
+    edges: HashMap<i8, u8>,
+    ladder: Option<(i8, i8)>,
+    water: Option<(i8, u8)>
 }
 
 impl MapTile {
@@ -170,17 +449,36 @@ impl MapTile {
         special_levels: BTreeMap<i8, i8>,
         masks: Option<Vec<MaskInfo>>
     ) -> MapTile {
-        MapTile { levels, down_levels, special_levels, masks, old_levels: None }
+        MapTile {
+            levels,
+            down_levels,
+            special_levels,
+            masks,
+            old_levels: None,
+            filled_levels: vec![],
+            platform_levels: vec![],
+            modifier: None,
+            entry_sides: HashMap::new(),
+            edges: HashMap::new(),
+            ladder: None,
+            water: None
+        }
     }
 
     pub fn from_data(map_tile_data: MapTileData, tile_size: &u32) -> MapTile {
-        MapTile::new(
+        let modifier = map_tile_data.modifier.and_then(MoveModifier::from_data);
+        let entry_sides = map_tile_data.entry_sides.into_iter().collect();
+        let edges = map_tile_data.edges.into_iter().collect();
+        let ladder = map_tile_data.ladder;
+        let water = map_tile_data.water;
+        let mut map_tile = MapTile::new(
             map_tile_data.levels,
             map_tile_data.down_levels.into_iter().collect(),
             map_tile_data.special_levels.into_iter()
                 .flat_map(|l| {
                     if is_odd(&l) {
-                        vec![(l - 1, l), (l + 1, l)]
+                        // there's no level above the top odd level to connect to
+                        [Some(l - 1), l.checked_add(1)].iter().flatten().map(|k| (*k, l)).collect()
                     }
                     else {
                         vec![(l, l)]
@@ -188,7 +486,13 @@ impl MapTile {
                 })
                 .collect(),
             MapTile::masks_from_data(map_tile_data.masks, tile_size)
-        )
+        );
+        map_tile.modifier = modifier;
+        map_tile.entry_sides = entry_sides;
+        map_tile.edges = edges;
+        map_tile.ladder = ladder;
+        map_tile.water = water;
+        map_tile
     }
 
     fn masks_from_data(mask_data: Vec<(u8, i8, bool, u8)>, tile_size: &u32) -> Option<Vec<MaskInfo>> {
@@ -206,8 +510,8 @@ impl MapTile {
             return Some(*l);
         }
         if is_odd(level) {
-            let level_range = (level - 1)..(level + 2);
-            for (_, l) in self.special_levels.range(level_range) {
+            let level_range = (level - 1)..=level.saturating_add(1);
+            if let Some((_, l)) = self.special_levels.range(level_range).next() {
                 return Some(*l);
             }
         }
@@ -215,13 +519,16 @@ impl MapTile {
     }
 
     fn get_down_level(&self, level: &i8) -> Option<u8> {
+        if self.platform_levels.contains(level) {
+            return None;
+        }
         if let Some(dl) = self.down_levels.get(level) {
             return Some(*dl)
         }
         None
     }
 
-    fn get_masking_indices(mask_infos: &Vec<MaskInfo>, sprite_z: i32, sprite_level: i8, sprite_upright: bool) -> Vec<u8> {
+    fn get_masking_indices(mask_infos: &[MaskInfo], sprite_z: i32, sprite_level: i8, sprite_upright: bool) -> Vec<u8> {
         mask_infos.iter()
             .filter(|mi| {
                 if mi.flat {
@@ -252,6 +559,18 @@ impl MapTile {
         if self.levels.contains(&level) {
             return (1, None);
         }
+        if self.platform_levels.contains(&level) {
+            return (1, None);
+        }
+        if let Some((bottom, top)) = self.ladder {
+            // only the ends can be walked onto, the levels in between are climbed
+            if level == bottom || level == top {
+                return (1, None);
+            }
+        }
+        if self.is_water_at(level) {
+            return (1, None);
+        }
         if self.down_levels.contains_key(&level) {
             return (1, None);
         }
@@ -267,14 +586,81 @@ impl MapTile {
         (0, None)
     }
 
+    // as get_validity_of, but for a climber, who can be at any level up a ladder
+    pub fn get_climbing_validity_of(&self, level: i8) -> (u8, Option<i8>) {
+        match self.ladder {
+            Some((bottom, top)) if level >= bottom && level <= top => (1, None),
+            _ => self.get_validity_of(level)
+        }
+    }
+
+    // lowest and highest levels of the tile, or None if it is empty (ie. a gap)
+    pub fn get_level_range(&self) -> Option<(i8, i8)> {
+        let levels = self.levels.iter()
+            .chain(self.platform_levels.iter())
+            .chain(self.down_levels.keys())
+            .chain(self.special_levels.values())
+            .copied();
+        let min_level = levels.clone().min()?;
+        let max_level = levels.max()?;
+        Some((min_level, max_level))
+    }
+
+    pub fn is_water_at(&self, level: i8) -> bool {
+        matches!(self.water, Some((surface, _)) if surface == level)
+    }
+
+    // depth of the water at the given level, if any
+    pub fn get_water_depth(&self, level: i8) -> Option<u8> {
+        match self.water {
+            Some((surface, depth)) if surface == level => Some(depth),
+            _ => None
+        }
+    }
+
+    // true if the tile can be entered through the given side at the given level
+    pub fn can_enter(&self, level: i8, side: u8) -> bool {
+        if let Some(sides) = self.entry_sides.get(&level) {
+            if sides & side == 0 {
+                return false;
+            }
+        }
+        self.can_leave(level, side)
+    }
+
+    // true if the tile can be left through the given side at the given level
+    pub fn can_leave(&self, level: i8, side: u8) -> bool {
+        match self.edges.get(&level) {
+            None => true,
+            Some(sides) => sides & side == 0
+        }
+    }
+
+    // replaces a down level with a walkable one, eg. when a hole is filled in - this outlives a rollback
+    pub fn fill_down_level(&mut self, level: i8) {
+        if self.down_levels.remove(&level).is_some() {
+            self.levels.push(level);
+            self.filled_levels.push(level);
+        }
+    }
+
     pub fn add_levels(&mut self, levels: &mut Vec<i8>) {
         self.old_levels = Some(self.levels.clone());
         self.levels.append(levels);
     }
 
+    pub fn set_platform_levels(&mut self, platform_levels: Vec<i8>) {
+        self.platform_levels = platform_levels;
+    }
+
     pub fn rollback(&mut self) {
         if let Some(levels) = self.old_levels.take() {
             self.levels = levels;
+            for level in self.filled_levels.iter() {
+                if !self.levels.contains(level) {
+                    self.levels.push(*level);
+                }
+            }
         }
     }
 
@@ -296,34 +682,51 @@ impl MapTile {
 }
 
 // enum with explicit discriminator
+#[allow(clippy::upper_case_acronyms)]
 enum Deferral {
     NONE = 0,
     DEFAULT = 1,
     DIAGONAL = 2,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug)]
 pub struct MoveResult {
-    #[wasm_bindgen(readonly)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
     pub valid: bool,
 
-    #[wasm_bindgen(readonly)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
     pub deferral: u8,
 
-    #[wasm_bindgen(readonly)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
     pub level: i8,
 
-    #[wasm_bindgen(readonly)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
     pub mx: i8,
 
-    #[wasm_bindgen(readonly)]
-    pub my: i8
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub my: i8,
+
+    // the part of (mx, my) caused by the tiles moved over rather than the requested move
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly, js_name = extraMx))]
+    pub extra_mx: i8,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly, js_name = extraMy))]
+    pub extra_my: i8
 }
 
 impl MoveResult {
     pub fn new(valid: bool, deferral: u8, level: i8, mx: i8, my: i8) -> MoveResult {
-        MoveResult { valid, deferral, level, mx, my }
+        MoveResult { valid, deferral, level, mx, my, extra_mx: 0, extra_my: 0 }
+    }
+
+    pub fn deltas(&self) -> (i8, i8) {
+        if self.valid {
+            (self.mx, self.my)
+        }
+        else {
+            (0, 0)
+        }
     }
 
     pub fn as_tuple(&self) -> (bool, u8, i8, i8, i8) {
@@ -331,9 +734,107 @@ impl MoveResult {
     }
 }
 
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug)]
+pub struct PushResult {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub valid: bool,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub id: u32,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub filled: bool,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub level: i8,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub left: i32,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub top: i32
+}
+
+impl PushResult {
+    pub fn new(valid: bool, id: u32, filled: bool, level: i8, rect: Rect) -> PushResult {
+        let (left, top) = rect.top_left();
+        PushResult { valid, id, filled, level, left, top }
+    }
+
+    pub fn as_tuple(&self) -> (bool, u32, bool, i8, i32, i32) {
+        (self.valid, self.id, self.filled, self.level, self.left, self.top)
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug)]
+pub struct JumpResult {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub valid: bool,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub level: i8,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub left: i32,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub top: i32,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub width: u32,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub height: u32
+}
+
+impl JumpResult {
+    pub fn new(valid: bool, level: i8, rect: Rect) -> JumpResult {
+        let (left, top) = rect.top_left();
+        JumpResult { valid, level, left, top, width: rect.width, height: rect.height }
+    }
+
+    pub fn as_tuple(&self) -> (bool, i8, i32, i32) {
+        (self.valid, self.level, self.left, self.top)
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl JumpResult {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = landingRect))]
+    pub fn landing_rect(&self) -> Rect {
+        Rect::new(self.left, self.top, self.width, self.height)
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug)]
+pub struct PlatformCarry {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub id: u32,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub mx: i8,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub my: i8,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(readonly))]
+    pub level: i8
+}
+
+impl PlatformCarry {
+    pub fn as_tuple(&self) -> (u32, i8, i8, i8) {
+        (self.id, self.mx, self.my, self.level)
+    }
+}
+
 // enum with explicit discriminator
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
 enum EventType {
     FALLING = 1,
+    DEEP_WATER = 2,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -348,6 +849,11 @@ impl MapEvent {
         MapEvent { event_type: EventType::FALLING as u8, value }
     }
 
+    // value is the depth of the water
+    pub fn deep_water_event(value: u8) -> MapEvent {
+        MapEvent { event_type: EventType::DEEP_WATER as u8, value }
+    }
+
     pub fn as_tuple(&self) -> (u8, u8) {
         (self.event_type, self.value)
     }
@@ -358,12 +864,19 @@ impl MapEvent {
 pub struct TileMasks {
     tx: u8,
     ty: u8,
-    tile_indices: Vec<u8>
+    tile_indices: Vec<u8>,
+    // y in pixels below which the part of the sprite over this tile is under water
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    waterline: Option<i32>
 }
 
 impl TileMasks {
     pub fn new(tx: u8, ty: u8, tile_indices: Vec<u8>) -> TileMasks {
-        TileMasks { tx, ty, tile_indices }
+        TileMasks { tx, ty, tile_indices, waterline: None }
+    }
+
+    pub fn with_waterline(tx: u8, ty: u8, tile_indices: Vec<u8>, waterline: i32) -> TileMasks {
+        TileMasks { tx, ty, tile_indices, waterline: Some(waterline) }
     }
 }
 
@@ -383,14 +896,20 @@ impl PlayMapData {
     }
 }
 
-#[wasm_bindgen(js_name = WasmPlayMap)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = WasmPlayMap))]
 pub struct PlayMap {
     rows: u8,
     cols: u8,
     tiles: Vec<MapTile>,
-    tile_size: u32
+    tile_size: u32,
+    entities: EntityRegistry,
+    platforms: BTreeMap<u32, Platform>,
+    platform_tiles: Vec<usize>,
+    recording: RefCell<Option<Replay>>
 }
 
+// The JsValue adapters, for the parts of the API that pass serde structures to and from JS
+#[cfg(feature = "wasm")]
 #[wasm_bindgen(js_class = WasmPlayMap)]
 impl PlayMap {
     #[wasm_bindgen(constructor)]
@@ -403,60 +922,56 @@ impl PlayMap {
         PlayMap::from_data(play_map_data)
     }
 
-    #[wasm_bindgen(js_name = applyMove)]
-    pub fn apply_move(&self, mx: i8, my: i8, level: i8, base_rect: Rect) -> MoveResult {
-//        log!("apply_move: received {} {} {} {:?}", mx, my, level, base_rect);
-        let new_base_rect = base_rect.move_rect(mx, my);
-        let (span_tiles, verticals, horizontals) = self.get_span_tiles_with_stripes(&new_base_rect);
-//        log!("span_tiles: {:?}", span_tiles);
-
-        let (valid, new_level) = self.is_span_valid(level, &span_tiles);
-        if valid {
-            let (mx_delta, my_delta) = base_rect.top_left_delta(&new_base_rect);
-            return if mx == 0 || my == 0 {
-                MoveResult::new(true, Deferral::NONE as u8, new_level, mx_delta, my_delta)
-            }
-            else {
-                MoveResult::new(true, Deferral::DIAGONAL as u8, new_level, mx_delta, my_delta)
-            }
+    #[wasm_bindgen(js_name = getEvent)]
+    pub fn get_js_event(&self, level: i8, base_rect: Rect) -> JsValue {
+//        log!("get_event: received {} {:?}", level, base_rect);
+        match self.get_event(level, base_rect) {
+            // JsValue::from_serde(&map_event).unwrap()
+            Some(map_event) => serde_wasm_bindgen::to_value(&map_event).unwrap(),
+            None => JsValue::NULL
         }
-//        log!("primary move not valid");
+    }
 
-        // movement invalid but we might be able to slide or shuffle
-        if mx == 0 {
-            // attempt shuffle left/right
-            let (new_level, new_base_rect) = self.is_vertical_valid(level, &base_rect, &verticals);
-//            log!("mx=0: {} {:?}", new_level, new_base_rect);
-            if let Some(rect) = new_base_rect {
-                let (mx_delta, my_delta) = base_rect.top_left_delta(&rect);
-                return MoveResult::new(true, Deferral::DEFAULT as u8, new_level, mx_delta, my_delta);
-            }
-        }
-        else if my == 0 {
-            // attempt shuffle up/down
-            let (new_level, new_base_rect) = self.is_horizontal_valid(level, &base_rect, &horizontals);
-//            log!("my=0: {} {:?}", new_level, new_base_rect);
-            if let Some(rect) = new_base_rect {
-                let (mx_delta, my_delta) = base_rect.top_left_delta(&rect);
-                return MoveResult::new(true, Deferral::DEFAULT as u8, new_level, mx_delta, my_delta);
-            }
-        }
-        else {
-            // diagonal movement - attempt slide
-            let (new_level, new_base_rect) = self.is_slide_valid(mx, my, level, &base_rect);
-//            log!("diagonal: {} {:?}", new_level, new_base_rect);
-            if let Some(rect) = new_base_rect {
-                let (mx_delta, my_delta) = base_rect.top_left_delta(&rect);
-                return MoveResult::new(true, Deferral::DEFAULT as u8, new_level, mx_delta, my_delta);
-            }
-        }
+    // same as applyMove, but explains what was checked and why the move was rejected
+    #[wasm_bindgen(js_name = explainMove)]
+    pub fn explain_js_move(&self, mx: i8, my: i8, level: i8, base_rect: Rect) -> JsValue {
+        let diagnostics = self.explain_move_for(&Mover::anonymous(), mx, my, level, base_rect);
+        serde_wasm_bindgen::to_value(&diagnostics).unwrap()
+    }
 
-        MoveResult::new(false, Deferral::NONE as u8, level, 0, 0)
+    #[wasm_bindgen(js_name = getSpriteMasks)]
+    pub fn get_js_sprite_masks(&self, rect: Rect, z: i32, level: i8, upright: bool) -> JsValue {
+//        log!("get_js_sprite_masks: received {:?} {} {} {}", rect, z, level, upright);
+        let sprite_masks = self.get_sprite_masks(rect, z, level, upright);
+//        log!("sprite masks: {:?}", sprite_masks);
+        // JsValue::from_serde(&sprite_masks).unwrap()
+        serde_wasm_bindgen::to_value(&sprite_masks).unwrap()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_class = WasmPlayMap))]
+impl PlayMap {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = applyMove))]
+    pub fn apply_move(&self, mx: i8, my: i8, level: i8, base_rect: Rect) -> MoveResult {
+        let result = self.apply_move_for(&Mover::anonymous(), mx, my, level, base_rect);
+        self.record(|| ReplayCall::apply_move(mx, my, level, &base_rect, result.as_tuple()));
+        result
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = applyMoveWithCapabilities))]
+    pub fn apply_move_with_capabilities(&self, mx: i8, my: i8, level: i8, base_rect: Rect, capabilities: &Capabilities) -> MoveResult {
+        self.apply_move_for(&Mover::anonymous().with_capabilities(*capabilities), mx, my, level, base_rect)
     }
 
-    #[wasm_bindgen(js_name = addLevelToTile)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = applyMoveWithConfig))]
+    pub fn apply_move_with_config(&self, mx: i8, my: i8, level: i8, base_rect: Rect, config: &MovementConfig) -> MoveResult {
+        self.apply_move_for(&Mover::anonymous().with_config(*config), mx, my, level, base_rect)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = addLevelToTile))]
     pub fn add_level_to_tile(&mut self, tx: u8, ty: u8, level: i8) {
 //        log!("add_level_to_tile: received {} {} {}", tx, ty, level);
+        self.record(|| ReplayCall::AddLevelToTile { tx, ty, level });
         let index = self.get_index(tx, ty);
         if let Some(tile) = self.tiles.get_mut(index) {
             let mut levels = vec![level];
@@ -464,67 +979,379 @@ impl PlayMap {
         }
     }
 
-    #[wasm_bindgen(js_name = rollbackTile)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = rollbackTile))]
     pub fn rollback_tile(&mut self, tx: u8, ty: u8) {
 //        log!("rollback_tile: received {} {}", tx, ty);
+        self.record(|| ReplayCall::RollbackTile { tx, ty });
         let index = self.get_index(tx, ty);
         if let Some(tile) = self.tiles.get_mut(index) {
             tile.rollback();
         }
     }
 
-    #[wasm_bindgen(js_name = getEvent)]
-    pub fn get_js_event(&self, level: i8, base_rect: Rect) -> JsValue {
-//        log!("get_event: received {} {:?}", level, base_rect);
-        let span_tiles = self.get_span_tiles(&base_rect);
-        let falling = span_tiles.iter().all(| tile | {
-            tile.get_down_level(&level).is_some()
-        });
-        if falling {
-            let down_level = span_tiles.get(0).unwrap().get_down_level(&level).unwrap();
-            let map_event = MapEvent::falling_event(down_level);
-            // JsValue::from_serde(&map_event).unwrap()
-            serde_wasm_bindgen::to_value(&map_event).unwrap()
-        }
-        else {
-            JsValue::NULL
+    // Records calls from the game until stopRecording, which returns them as replay text.
+    // Replays run against the map as loaded, so this returns false and records nothing once
+    // tiles have been changed or sprites or platforms added. Sprite and platform calls aren't
+    // recorded either, so a replay only covers maps that are left without them.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = startRecording))]
+    pub fn start_recording(&mut self) -> bool {
+        if !self.is_as_loaded() {
+            return false;
         }
+        *self.recording.get_mut() = Some(Replay::new(self.tile_size));
+        true
     }
 
-    #[wasm_bindgen(js_name = getSpriteMasks)]
-    pub fn get_js_sprite_masks(&self, rect: Rect, z: i32, level: i8, upright: bool) -> JsValue {
-//        log!("get_js_sprite_masks: received {:?} {} {} {}", rect, z, level, upright);
-        let sprite_masks = self.get_sprite_masks(rect, z, level, upright);
-//        log!("sprite masks: {:?}", sprite_masks);
-        // JsValue::from_serde(&sprite_masks).unwrap()
-        serde_wasm_bindgen::to_value(&sprite_masks).unwrap()
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = stopRecording))]
+    pub fn stop_recording(&mut self) -> Option<String> {
+        self.take_recording().map(|replay| replay.to_string())
     }
-}
 
-pub struct Shuffle {
-    index1: usize,
-    shuffle1: i8,
-    index2: usize,
-    shuffle2: i8
-}
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = insertSprite))]
+    pub fn insert_sprite(&mut self, id: u32, level: i8, base_rect: Rect) {
+        self.entities.insert(id, Entity::sprite(base_rect, level));
+    }
 
-const MIN_SHUFFLE: Shuffle = Shuffle { index1: 0, shuffle1: -2, index2: 1, shuffle2: 2 };
-const MAX_SHUFFLE: Shuffle = Shuffle { index1: 1, shuffle1: 2, index2: 0, shuffle2: -2 };
+    // solid sprites block apply_move in the same way as walls
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = insertObstacle))]
+    pub fn insert_obstacle(&mut self, id: u32, level: i8, base_rect: Rect) {
+        self.entities.insert(id, Entity::obstacle(base_rect, level));
+    }
 
-impl Shuffle {
-    pub fn get_shuffle(min_diff: i32, max_diff: i32) -> Shuffle {
-        if max_diff < min_diff {
-            MIN_SHUFFLE
-        }
-        else {
-            MAX_SHUFFLE
-        }
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = insertPushable))]
+    pub fn insert_pushable(&mut self, id: u32, level: i8, base_rect: Rect) {
+        self.entities.insert(id, Entity::pushable(base_rect, level));
     }
-}
+
+    // pushes any pushable sprite in the way of the given move - the mover's own move
+    // should then be attempted with applyMove
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = applyPush))]
+    pub fn apply_push(&mut self, mx: i8, my: i8, level: i8, base_rect: Rect) -> Option<PushResult> {
+        let new_base_rect = base_rect.move_rect(mx, my);
+        let id = self.entities.overlapping(&new_base_rect, level).into_iter()
+            .find(|id| self.entities.get(*id).unwrap().is_pushable())?;
+        let block = *self.entities.get(id).unwrap();
+        if mx != 0 && my != 0 {
+            // blocks can't be pushed diagonally
+            return Some(PushResult::new(false, id, false, block.level(), block.rect()));
+        }
+        let block_rect = block.rect().move_rect(mx, my);
+        let (valid, block_level) = self.is_step_valid_for(&Mover::sprite(id), block.level(), block.rect(), block_rect);
+        if !valid || !self.get_map_rect().contains_rect(&block_rect) {
+            return Some(PushResult::new(false, id, false, block.level(), block.rect()));
+        }
+        if self.fill_down_levels(block_level, &block_rect) {
+            self.entities.remove(id);
+            return Some(PushResult::new(true, id, true, block_level, block_rect));
+        }
+        self.entities.move_to(id, block_rect, block_level);
+        Some(PushResult::new(true, id, false, block_level, block_rect))
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = addPlatform))]
+    pub fn add_platform(&mut self, id: u32, level: i8, rect: Rect, speed: u8) {
+        self.platforms.insert(id, Platform::new(rect, level, speed));
+        self.refresh_platform_tiles();
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = addPlatformWaypoint))]
+    pub fn add_platform_waypoint(&mut self, id: u32, x: i32, y: i32, level: i8, wait: u32) -> bool {
+        match self.platforms.get_mut(&id) {
+            None => false,
+            Some(platform) => {
+                platform.add_waypoint(Waypoint::new(x, y, level, wait));
+                true
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = removePlatform))]
+    pub fn remove_platform(&mut self, id: u32) -> bool {
+        let removed = self.platforms.remove(&id).is_some();
+        self.refresh_platform_tiles();
+        removed
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = tickPlatforms))]
+    pub fn tick_platforms(&mut self) {
+        self.platforms.values_mut().for_each(|platform| platform.tick());
+        self.refresh_platform_tiles();
+    }
+
+    // the movement to apply to a sprite that was standing on a platform before the last tick
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = getPlatformCarry))]
+    pub fn get_platform_carry(&self, level: i8, base_rect: Rect) -> Option<PlatformCarry> {
+        self.platforms.iter()
+            .find(|(_, platform)| platform.is_carrying(level, &base_rect))
+            .map(|(id, platform)| {
+                let (mx, my, level) = platform.carry();
+                PlatformCarry { id: *id, mx, my, level }
+            })
+    }
+
+    // moves a registered sprite, applying the movement modifiers of the tile it is on
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = applySpriteMove))]
+    pub fn apply_sprite_move(&mut self, id: u32, mx: i8, my: i8) -> MoveResult {
+        let entity = match self.entities.get(id) {
+            None => return MoveResult::new(false, Deferral::NONE as u8, 0, 0, 0),
+            Some(entity) => *entity
+        };
+        let mover = Mover::sprite(id).with_config(entity.config()).with_capabilities(entity.capabilities());
+        let (rect, level) = (entity.rect(), entity.level());
+        let modifier = self.get_modifier(&rect);
+        let (imx, imy) = match modifier {
+            Some(MoveModifier::Ice) if entity.momentum() != (0, 0) => entity.momentum(),
+            Some(MoveModifier::Slow(percent)) => (MoveModifier::slow(mx, percent), MoveModifier::slow(my, percent)),
+            _ => (mx, my)
+        };
+        let mut result = self.apply_move_for(&mover, imx, imy, level, rect);
+        let momentum = match modifier {
+            Some(MoveModifier::Ice) if result.valid && result.deferral != Deferral::DEFAULT as u8 => (imx, imy),
+            _ => (0, 0)
+        };
+        // the extra is whatever ice or slow changed the requested move by, on the axes it got along
+        let (mut extra_mx, mut extra_my) = if result.valid && result.deferral != Deferral::DEFAULT as u8 {
+            let (rmx, rmy) = result.deltas();
+            (
+                if rmx == 0 { 0 } else { imx as i16 - mx as i16 },
+                if rmy == 0 { 0 } else { imy as i16 - my as i16 }
+            )
+        }
+        else {
+            (0, 0)
+        };
+        if let Some(MoveModifier::Conveyor(cx, cy)) = modifier {
+            let (rmx, rmy) = result.deltas();
+            let new_level = if result.valid { result.level } else { level };
+            let conveyed = self.apply_move_for(&mover, cx, cy, new_level, rect.move_rect(rmx, rmy));
+            if conveyed.valid && conveyed.deferral != Deferral::DEFAULT as u8 {
+                let (cmx, cmy) = conveyed.deltas();
+                let deferral = if result.valid { result.deferral } else { conveyed.deferral };
+                result = MoveResult::new(true, deferral, conveyed.level, rmx.saturating_add(cmx), rmy.saturating_add(cmy));
+                extra_mx += cmx as i16;
+                extra_my += cmy as i16;
+            }
+        }
+        result.extra_mx = extra_mx.clamp(i8::MIN as i16, i8::MAX as i16) as i8;
+        result.extra_my = extra_my.clamp(i8::MIN as i16, i8::MAX as i16) as i8;
+        if result.valid {
+            self.entities.move_to(id, rect.move_rect(result.mx, result.my), result.level);
+        }
+        self.entities.set_momentum(id, momentum);
+        result
+    }
+
+    // checks a jump of distance tiles in direction (dx, dy), landing at the same or a lower level
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = getJump))]
+    pub fn get_jump(&self, dx: i8, dy: i8, distance: u8, level: i8, base_rect: Rect) -> JumpResult {
+        let jump = distance as i32 * self.tile_size as i32;
+        let landing_rect = Rect::new(
+            base_rect.left + dx.signum() as i32 * jump,
+            base_rect.top + dy.signum() as i32 * jump,
+            base_rect.width,
+            base_rect.height
+        );
+        if (dx == 0 && dy == 0) || !self.get_map_rect().contains_rect(&landing_rect) {
+            return JumpResult::new(false, level, base_rect);
+        }
+        if !self.is_jump_clear(level, &base_rect, &landing_rect) {
+            return JumpResult::new(false, level, base_rect);
+        }
+        let landing_tiles = self.get_span_tiles(&landing_rect);
+        let floor = landing_tiles.clone()
+            .filter_map(|tile| tile.get_level_range())
+            .map(|(min_level, _)| min_level)
+            .min();
+        if let Some(floor) = floor {
+            // the highest landing level wins
+            for landing_level in (floor..=level).rev() {
+                let (valid, new_level) = self.is_tiles_valid_for(&Capabilities::default(), landing_level, landing_tiles.clone());
+                if valid && !self.is_blocked(&Mover::anonymous(), &landing_rect, new_level) {
+                    return JumpResult::new(true, new_level, landing_rect);
+                }
+            }
+        }
+        JumpResult::new(false, level, base_rect)
+    }
+
+    // moves a registered sprite by (fmx, fmy) in 1/256ths of a pixel: the whole pixels are
+    // applied as for applySpriteMove and the rest is kept for next time
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = applySpriteMoveFixed))]
+    pub fn apply_sprite_move_fixed(&mut self, id: u32, fmx: i32, fmy: i32) -> MoveResult {
+        let (mx, my) = match self.entities.accumulate(id, fmx, fmy) {
+            None => return MoveResult::new(false, Deferral::NONE as u8, 0, 0, 0),
+            Some(deltas) => deltas
+        };
+        let result = self.apply_sprite_move(id, mx, my);
+        if !result.valid || result.deferral == Deferral::DEFAULT as u8 {
+            // blocked or shuffled, so the remainder no longer applies
+            self.entities.clear_remainder(id);
+        }
+        result
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = setSpriteMovementConfig))]
+    pub fn set_sprite_movement_config(&mut self, id: u32, config: &MovementConfig) -> bool {
+        self.entities.set_config(id, *config)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = setSpriteCanSwim))]
+    pub fn set_sprite_can_swim(&mut self, id: u32, can_swim: bool) -> bool {
+        match self.entities.get(id) {
+            None => false,
+            Some(entity) => {
+                let capabilities = Capabilities { can_swim, ..entity.capabilities() };
+                self.entities.set_capabilities(id, capabilities)
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = setSpriteCapabilities))]
+    pub fn set_sprite_capabilities(&mut self, id: u32, capabilities: &Capabilities) -> bool {
+        self.entities.set_capabilities(id, *capabilities)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = setSpriteSolid))]
+    pub fn set_sprite_solid(&mut self, id: u32, solid: bool) -> bool {
+        self.entities.set_solid(id, solid)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = moveSprite))]
+    pub fn move_sprite(&mut self, id: u32, level: i8, base_rect: Rect) -> bool {
+        self.entities.move_to(id, base_rect, level)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = removeSprite))]
+    pub fn remove_sprite(&mut self, id: u32) -> bool {
+        self.entities.remove(id)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = getSpritesOverlapping))]
+    pub fn get_sprites_overlapping(&self, level: i8, rect: Rect) -> Vec<u32> {
+        self.entities.overlapping(&rect, level)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = getSpriteCollisions))]
+    pub fn get_sprite_collisions(&self, id: u32, mx: i8, my: i8) -> Vec<u32> {
+        self.entities.collisions(id, mx, my)
+    }
+}
+
+// identifies who is making a move, so that registered sprites don't block themselves,
+// and how much help they get getting around corners
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mover {
+    id: Option<u32>,
+    config: MovementConfig,
+    capabilities: Capabilities
+}
+
+impl Mover {
+    pub fn anonymous() -> Mover {
+        Mover { id: None, config: MovementConfig::default(), capabilities: Capabilities::default() }
+    }
+
+    pub fn sprite(id: u32) -> Mover {
+        Mover { id: Some(id), config: MovementConfig::default(), capabilities: Capabilities::default() }
+    }
+
+    pub fn with_config(self, config: MovementConfig) -> Mover {
+        Mover { config, ..self }
+    }
+
+    pub fn with_capabilities(self, capabilities: Capabilities) -> Mover {
+        Mover { capabilities, ..self }
+    }
+
+    pub fn with_swim(self, can_swim: bool) -> Mover {
+        Mover { capabilities: Capabilities { can_swim, ..self.capabilities }, ..self }
+    }
+}
+
+// stripes to try shuffling towards, in order, with the shuffle for each and how much of
+// the rect overlaps the stripe being shuffled away from
+pub struct Shuffle {
+    index1: usize,
+    shuffle1: i8,
+    overlap1: i32,
+    index2: usize,
+    shuffle2: i8,
+    overlap2: i32
+}
+
+// A run of ladder tiles in one column, from run_top to run_bottom in pixels. Climbers
+// get a level between bottom and top depending on how far up the run they are.
+#[derive(Debug, Clone, Copy)]
+struct Ladder {
+    bottom: i8,
+    top: i8,
+    run_top: i32,
+    run_bottom: i32
+}
+
+impl Ladder {
+    fn contains(&self, level: i8) -> bool {
+        level >= self.bottom && level <= self.top
+    }
+
+    // on the ladder rather than at either end of it
+    fn is_climbing(&self, level: i8) -> bool {
+        level > self.bottom && level < self.top
+    }
+
+    fn level_at(&self, rect: &Rect) -> i8 {
+        let height = max(self.run_bottom - self.run_top, 1);
+        let climbed = (self.run_bottom - rect.bottom).clamp(0, height);
+        let range = (self.top - self.bottom) as i32;
+        self.bottom + ((range * climbed + height / 2) / height) as i8
+    }
+
+    // the nearest end of the ladder, for when rect is partly on and partly off it
+    fn end_at(&self, rect: &Rect) -> i8 {
+        if (self.level_at(rect) - self.bottom) * 2 >= self.top - self.bottom {
+            self.top
+        }
+        else {
+            self.bottom
+        }
+    }
+}
+
+impl Shuffle {
+    pub fn get_shuffle(min_diff: i32, max_diff: i32, nudge: i8) -> Shuffle {
+        if max_diff < min_diff {
+            Shuffle { index1: 0, shuffle1: -nudge, overlap1: max_diff, index2: 1, shuffle2: nudge, overlap2: min_diff }
+        }
+        else {
+            Shuffle { index1: 1, shuffle1: nudge, overlap1: min_diff, index2: 0, shuffle2: -nudge, overlap2: max_diff }
+        }
+    }
+}
 
 impl PlayMap {
     pub fn new(rows: u8, cols: u8, tiles: Vec<MapTile>, tile_size: u32) -> PlayMap {
-        PlayMap { rows, cols, tiles, tile_size }
+        PlayMap {
+            rows,
+            cols,
+            tiles,
+            tile_size,
+            entities: EntityRegistry::new(rows, cols, tile_size),
+            platforms: BTreeMap::new(),
+            platform_tiles: vec![],
+            recording: RefCell::new(None)
+        }
+    }
+
+    pub fn entities(&self) -> &EntityRegistry {
+        &self.entities
+    }
+
+    // the tile at the given tile coordinates, or None if they're off the map
+    pub fn get_tile_at(&self, tx: i32, ty: i32) -> Option<&MapTile> {
+        if tx < 0 || ty < 0 || tx >= self.cols as i32 || ty >= self.rows as i32 {
+            return None;
+        }
+        self.tiles.get(self.get_index(tx as u8, ty as u8))
     }
 
     pub fn from_data(play_map_data: PlayMapData) -> PlayMap {
@@ -539,96 +1366,586 @@ impl PlayMap {
         )
     }
 
+    pub fn apply_move_for(&self, mover: &Mover, mx: i8, my: i8, level: i8, base_rect: Rect) -> MoveResult {
+        self.apply_traced_move(mover, mx, my, level, base_rect, None)
+    }
+
+    pub fn explain_move_for(&self, mover: &Mover, mx: i8, my: i8, level: i8, base_rect: Rect) -> MoveDiagnostics {
+        let mut trace = MoveTrace::new();
+        let result = self.apply_traced_move(mover, mx, my, level, base_rect, Some(&mut trace));
+        MoveDiagnostics::from_trace(&result, trace)
+    }
+
+    // apply_move_for, noting what was checked along the way when there's a trace to fill in
+    fn apply_traced_move(&self, mover: &Mover, mx: i8, my: i8, level: i8, base_rect: Rect, mut trace: Option<&mut MoveTrace>) -> MoveResult {
+//        log!("apply_move: received {} {} {} {:?}", mx, my, level, base_rect);
+        if let Some(result) = self.apply_ladder_move(mover, mx, my, level, &base_rect, trace.as_deref_mut()) {
+            return result;
+        }
+        let new_base_rect = base_rect.move_rect(mx, my);
+        let span_tiles = self.get_span_tiles(&new_base_rect);
+//        log!("span_tiles: {:?}", span_tiles);
+
+        let (valid, new_level) = self.is_tiles_valid_for(&mover.capabilities, level, span_tiles);
+        let rejection = self.get_rejection(mover, valid, (level, new_level), &base_rect, &new_base_rect);
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.check(self.get_tile_checks(level, &new_base_rect), rejection);
+        }
+        if rejection.is_none() {
+            let (mx_delta, my_delta) = base_rect.top_left_delta(&new_base_rect);
+            return if mx == 0 || my == 0 {
+                MoveResult::new(true, Deferral::NONE as u8, new_level, mx_delta, my_delta)
+            }
+            else {
+                MoveResult::new(true, Deferral::DIAGONAL as u8, new_level, mx_delta, my_delta)
+            }
+        }
+//        log!("primary move not valid");
+
+        // movement invalid but we might be able to slide or shuffle
+        if !mover.config.assist {
+            if let Some(trace) = trace {
+                let fallback = if mx == 0 || my == 0 { Fallback::Shuffle } else { Fallback::Slide };
+                trace.attempt(FallbackAttempt::new(fallback, Some(FallbackFailure::Disabled)));
+            }
+            return MoveResult::new(false, Deferral::NONE as u8, level, 0, 0);
+        }
+        if mx == 0 || my == 0 {
+            let shuffle = if mx == 0 {
+                // attempt shuffle left/right
+                self.is_vertical_valid(mover, level, &base_rect, &new_base_rect)
+            }
+            else {
+                // attempt shuffle up/down
+                self.is_horizontal_valid(mover, level, &base_rect, &new_base_rect)
+            };
+//            log!("shuffle: {:?}", shuffle);
+            match shuffle {
+                Ok((new_level, rect)) => {
+                    let (mx_delta, my_delta) = base_rect.top_left_delta(&rect);
+                    if let Some(trace) = trace {
+                        trace.attempt(FallbackAttempt::new(Fallback::Shuffle, None).with_step(mx_delta, my_delta));
+                    }
+                    return MoveResult::new(true, Deferral::DEFAULT as u8, new_level, mx_delta, my_delta);
+                }
+                Err(failure) => if let Some(trace) = trace {
+                    trace.attempt(FallbackAttempt::new(Fallback::Shuffle, Some(failure)));
+                }
+            }
+        }
+        else {
+            // diagonal movement - attempt slide
+            let (new_level, new_base_rect) = self.is_slide_valid(mover, mx, my, level, &base_rect, trace);
+//            log!("diagonal: {} {:?}", new_level, new_base_rect);
+            if let Some(rect) = new_base_rect {
+                let (mx_delta, my_delta) = base_rect.top_left_delta(&rect);
+                return MoveResult::new(true, Deferral::DEFAULT as u8, new_level, mx_delta, my_delta);
+            }
+        }
+
+        MoveResult::new(false, Deferral::NONE as u8, level, 0, 0)
+    }
+
+    // why a step from rect to new_rect is rejected, given whether its tiles are valid: levels
+    // are the ones the step leaves and enters at
+    fn get_rejection(&self, mover: &Mover, valid: bool, levels: (i8, i8), rect: &Rect, new_rect: &Rect) -> Option<Rejection> {
+        let (level, new_level) = levels;
+        if !valid {
+            Some(Rejection::Tiles)
+        }
+        else if self.is_blocked(mover, new_rect, new_level) {
+            Some(Rejection::Sprite)
+        }
+        else if !self.is_crossing_valid(level, new_level, rect, new_rect) {
+            Some(Rejection::Crossing)
+        }
+        else {
+            None
+        }
+    }
+
+    fn get_tile_checks(&self, level: i8, rect: &Rect) -> Vec<TileCheck> {
+        self.get_span_tiles_with_position(rect)
+            .map(|(tx, ty, tile)| TileCheck::new(tx, ty, level, tile.get_validity_of(level)))
+            .collect()
+    }
+
+    // handles moves on a ladder, or None if the move doesn't involve climbing
+    fn apply_ladder_move(&self, mover: &Mover, mx: i8, my: i8, level: i8, base_rect: &Rect, trace: Option<&mut MoveTrace>) -> Option<MoveResult> {
+        let ladder = self.get_ladder(base_rect).filter(|ladder| ladder.contains(level))?;
+        let climbing = ladder.is_climbing(level);
+        if my == 0 {
+            return if climbing {
+                // can't move sideways on a ladder
+                if let Some(trace) = trace {
+                    trace.check(vec![], Some(Rejection::Ladder));
+                }
+                Some(MoveResult::new(false, Deferral::NONE as u8, level, 0, 0))
+            }
+            else {
+                None
+            };
+        }
+        let new_base_rect = base_rect.move_rect(0, my);
+        let span_tiles = self.get_span_tiles(&new_base_rect);
+        let new_level = if span_tiles.clone().all(|tile| tile.ladder.is_some()) {
+            ladder.level_at(&new_base_rect)
+        }
+        else {
+            ladder.end_at(&new_base_rect)
+        };
+        if !climbing && new_level == level {
+            // still at the bottom or top, so it's a normal move
+            return None;
+        }
+        let ladder_level = new_level;
+        let (valid, new_level) = if ladder.is_climbing(ladder_level) {
+            (span_tiles.clone().all(|tile| tile.get_climbing_validity_of(ladder_level).0 == 1), ladder_level)
+        }
+        else {
+            self.is_tiles_valid_for(&mover.capabilities, ladder_level, span_tiles)
+        };
+        let rejection = self.get_rejection(mover, valid, (level, new_level), base_rect, &new_base_rect);
+        if let Some(trace) = trace {
+            let tiles = self.get_span_tiles_with_position(&new_base_rect)
+                .map(|(tx, ty, tile)| TileCheck::new(tx, ty, ladder_level, tile.get_climbing_validity_of(ladder_level)))
+                .collect();
+            trace.check(tiles, rejection);
+        }
+        if rejection.is_none() {
+            let (_, my_delta) = base_rect.top_left_delta(&new_base_rect);
+            let deferral = if mx == 0 {
+                Deferral::NONE
+            }
+            else {
+                // the sideways part of the move was dropped
+                Deferral::DEFAULT
+            };
+            return Some(MoveResult::new(true, deferral as u8, new_level, 0, my_delta));
+        }
+        Some(MoveResult::new(false, Deferral::NONE as u8, level, 0, 0))
+    }
+
+    // the ladder run under rect, if any
+    fn get_ladder(&self, rect: &Rect) -> Option<Ladder> {
+        let (tx, ty, (bottom, top)) = self.get_span_tiles_with_position(rect)
+            .find_map(|(tx, ty, tile)| tile.ladder.map(|ladder| (tx, ty, ladder)))?;
+        let is_run = |y: u8| self.tiles.get(self.get_index(tx, y)).is_some_and(|tile| tile.ladder == Some((bottom, top)));
+        let mut run_top = ty;
+        while run_top > 0 && is_run(run_top - 1) {
+            run_top -= 1;
+        }
+        let mut run_bottom = ty;
+        while run_bottom + 1 < self.rows && is_run(run_bottom + 1) {
+            run_bottom += 1;
+        }
+        let ts = self.tile_size as i32;
+        Some(Ladder { bottom, top, run_top: run_top as i32 * ts, run_bottom: (run_bottom as i32 + 1) * ts })
+    }
+
+    pub fn get_event(&self, level: i8, base_rect: Rect) -> Option<MapEvent> {
+        let event = self.find_event(level, &base_rect);
+        self.record(|| ReplayCall::get_event(level, &base_rect, event.as_ref().map(|e| e.as_tuple())));
+        event
+    }
+
+    // the last call to start_recording and every apply_move, add_level_to_tile, rollback_tile
+    // and get_event since, or None if not recording
+    pub fn take_recording(&mut self) -> Option<Replay> {
+        self.recording.get_mut().take()
+    }
+
+    // true if nothing has changed the map since it was loaded
+    fn is_as_loaded(&self) -> bool {
+        self.entities.is_empty() && self.platforms.is_empty()
+            && self.tiles.iter().all(|tile| tile.old_levels.is_none() && tile.filled_levels.is_empty())
+    }
+
+    fn record<F: FnOnce() -> ReplayCall>(&self, call: F) {
+        if let Some(replay) = self.recording.borrow_mut().as_mut() {
+            replay.push(call());
+        }
+    }
+
+    fn find_event(&self, level: i8, base_rect: &Rect) -> Option<MapEvent> {
+        let mut span_tiles = self.get_span_tiles(base_rect);
+        let first_tile = span_tiles.clone().next()?;
+        let falling = span_tiles.clone().all(| tile | {
+            tile.get_down_level(&level).is_some()
+        });
+        if falling {
+            let down_level = first_tile.get_down_level(&level).unwrap();
+            return Some(MapEvent::falling_event(down_level));
+        }
+        // deep water is at least half a tile deep under the whole base rect
+        let depth = span_tiles.try_fold(u8::MAX, |depth, tile| {
+            tile.get_water_depth(level).map(|d| min(depth, d))
+        })?;
+        if depth as u32 * 2 >= self.tile_size {
+            return Some(MapEvent::deep_water_event(depth));
+        }
+        None
+    }
+
     pub fn is_move_valid(&self, level: i8, base_rect: Rect) -> (bool, i8) {
-        self.is_span_valid(level, &self.get_span_tiles(&base_rect))
+        self.is_move_valid_for(&Mover::anonymous(), level, base_rect)
+    }
+
+    pub fn is_move_valid_for(&self, mover: &Mover, level: i8, base_rect: Rect) -> (bool, i8) {
+        let (valid, new_level) = self.is_tiles_valid_for(&mover.capabilities, level, self.get_span_tiles(&base_rect));
+        (valid && !self.is_blocked(mover, &base_rect, new_level), new_level)
+    }
+
+    // like is_move_valid, but also honours one-way tiles and edges when stepping from base_rect
+    pub fn is_step_valid(&self, level: i8, base_rect: Rect, new_base_rect: Rect) -> (bool, i8) {
+        self.is_step_valid_for(&Mover::anonymous(), level, base_rect, new_base_rect)
+    }
+
+    pub fn is_step_valid_for(&self, mover: &Mover, level: i8, base_rect: Rect, new_base_rect: Rect) -> (bool, i8) {
+        let (valid, new_level) = self.is_tiles_valid_for(&mover.capabilities, level, self.get_span_tiles(&new_base_rect));
+        (self.get_rejection(mover, valid, (level, new_level), &base_rect, &new_base_rect).is_none(), new_level)
+    }
+
+    // checks the tile sides crossed when moving from rect to new_rect: sides are left at
+    // level and entered at new_level
+    fn is_crossing_valid(&self, level: i8, new_level: i8, rect: &Rect, new_rect: &Rect) -> bool {
+        let (tx1, ty1, tx2, ty2) = self.convert_rect(rect);
+        let (ntx1, nty1, ntx2, nty2) = self.convert_rect(new_rect);
+        let lefts = (tx2..ntx2).flat_map(|x| (nty1..nty2).map(move |y| (x, y, Side::LEFT)));
+        let rights = (ntx1..tx1).flat_map(|x| (nty1..nty2).map(move |y| (x, y, Side::RIGHT)));
+        let tops = (ty2..nty2).flat_map(|y| (ntx1..ntx2).map(move |x| (x, y, Side::TOP)));
+        let bottoms = (nty1..ty1).flat_map(|y| (ntx1..ntx2).map(move |x| (x, y, Side::BOTTOM)));
+        lefts.chain(rights).chain(tops).chain(bottoms)
+            .all(|(x, y, side)| self.is_side_open(level, new_level, x, y, side))
+    }
+
+    // true if tile (x, y) can be entered through side from its neighbour on that side
+    fn is_side_open(&self, level: i8, new_level: i8, x: u8, y: u8, side: Side) -> bool {
+        let (from, opposite) = match side {
+            Side::TOP => (y.checked_sub(1).map(|fy| (x, fy)), Side::BOTTOM),
+            Side::RIGHT => ((x + 1 < self.cols).then_some((x + 1, y)), Side::LEFT),
+            Side::BOTTOM => ((y + 1 < self.rows).then_some((x, y + 1)), Side::TOP),
+            Side::LEFT => (x.checked_sub(1).map(|fx| (fx, y)), Side::RIGHT),
+        };
+        // a missing tile counts as closed
+        match self.tiles.get(self.get_index(x, y)) {
+            Some(tile) if tile.can_enter(new_level, side as u8) => (),
+            _ => return false
+        }
+        match from {
+            None => true,
+            Some((fx, fy)) => self.tiles.get(self.get_index(fx, fy))
+                .is_some_and(|tile| tile.can_leave(level, opposite as u8))
+        }
+    }
+
+    // tiles under a platform are walkable at the platform's level
+    fn refresh_platform_tiles(&mut self) {
+        for index in self.platform_tiles.drain(..) {
+            self.tiles[index].set_platform_levels(vec![]);
+        }
+        let mut platform_levels: BTreeMap<usize, Vec<i8>> = BTreeMap::new();
+        for platform in self.platforms.values() {
+            let (tx1, ty1, tx2, ty2) = self.convert_rect(&platform.rect());
+            for y in ty1..ty2 {
+                for x in tx1..tx2 {
+                    platform_levels.entry(self.get_index(x, y)).or_default().push(platform.level());
+                }
+            }
+        }
+        for (index, levels) in platform_levels {
+            if let Some(tile) = self.tiles.get_mut(index) {
+                tile.set_platform_levels(levels);
+                self.platform_tiles.push(index);
+            }
+        }
+    }
+
+    // true if every tile passed over between the take off and landing spans can be cleared,
+    // following the flight path a pixel at a time so that diagonal jumps don't clip corners
+    fn is_jump_clear(&self, level: i8, base_rect: &Rect, landing_rect: &Rect) -> bool {
+        let (bx1, by1, bx2, by2) = self.convert_rect(base_rect);
+        let (lx1, ly1, lx2, ly2) = self.convert_rect(landing_rect);
+        let within = |x: u8, y: u8, (x1, y1, x2, y2): (u8, u8, u8, u8)| x >= x1 && x < x2 && y >= y1 && y < y2;
+        let (jx, jy) = (landing_rect.left - base_rect.left, landing_rect.top - base_rect.top);
+        let mut last_range = (bx1, by1, bx2, by2);
+        for step in 1..jx.abs().max(jy.abs()) {
+            let tile_range = self.convert_rect(&base_rect.offset(jx.signum() * step, jy.signum() * step));
+            if tile_range == last_range {
+                continue;
+            }
+            last_range = tile_range;
+            let clear = self.get_range_tiles(tile_range)
+                .filter(|(x, y, _)| !within(*x, *y, (bx1, by1, bx2, by2)) && !within(*x, *y, (lx1, ly1, lx2, ly2)))
+                .all(|(_, _, tile)| match tile.get_level_range() {
+                    None => true,
+                    Some((_, max_level)) => max_level <= level.saturating_add(JUMP_CLEARANCE)
+                });
+            if !clear {
+                return false;
+            }
+        }
+        true
+    }
+
+    // the modifier of the tile under the center of rect
+    fn get_modifier(&self, rect: &Rect) -> Option<MoveModifier> {
+        let (cx, cy) = rect.center();
+        let (tx, ty, tx2, ty2) = Rect::new(cx, cy, 1, 1).to_tile_range(self.tile_size, self.cols, self.rows).as_tuple();
+        if tx >= tx2 || ty >= ty2 {
+            return None;
+        }
+        self.tiles.get(self.get_index(tx, ty)).and_then(|tile| tile.modifier)
+    }
+
+    fn get_map_rect(&self) -> Rect {
+        Rect::new(0, 0, self.cols as u32 * self.tile_size, self.rows as u32 * self.tile_size)
+    }
+
+    // fills in the tiles under rect if they are all down levels (ie. a hole) at the given level
+    fn fill_down_levels(&mut self, level: i8, rect: &Rect) -> bool {
+        let (tx1, ty1, tx2, ty2) = self.convert_rect(rect);
+        let indices: Vec<usize> = (ty1..ty2)
+            .flat_map(|y| (tx1..tx2).map(move |x| (x, y)))
+            .map(|(x, y)| self.get_index(x, y))
+            .collect();
+        let hole = !indices.is_empty() && indices.iter().all(|index| {
+            self.tiles.get(*index).and_then(|tile| tile.get_down_level(&level)).is_some()
+        });
+        if hole {
+            for index in indices {
+                self.tiles[index].fill_down_level(level);
+            }
+        }
+        hole
+    }
+
+    fn is_blocked(&self, mover: &Mover, rect: &Rect, level: i8) -> bool {
+        !self.entities.is_empty() && self.entities.is_blocked(rect, level, mover.id)
     }
 
     pub fn is_span_valid(&self, level: i8, span_tiles: &[&MapTile]) -> (bool, i8) {
+        self.is_span_valid_for(&Capabilities::default(), level, span_tiles)
+    }
+
+    pub fn is_span_valid_for(&self, capabilities: &Capabilities, level: i8, span_tiles: &[&MapTile]) -> (bool, i8) {
+        self.is_tiles_valid_for(capabilities, level, span_tiles.iter().copied())
+    }
+
+    // is_span_valid_for over the tiles as they come, so spans can be checked straight off the map
+    fn is_tiles_valid_for<'a, T>(&self, capabilities: &Capabilities, level: i8, span_tiles: T) -> (bool, i8)
+        where T: Iterator<Item = &'a MapTile> + Clone {
+        // no tiles means the rect is off the map
+        if span_tiles.clone().next().is_none() {
+            return (false, level);
+        }
+        let (valid, new_level) = self.is_span_valid_at(capabilities, level, span_tiles.clone());
+        if valid {
+            return (valid, new_level);
+        }
+        if capabilities.max_step_up > 0 {
+            // the whole span has to be valid at one level up to a step away, the highest first
+            let step = min(capabilities.max_step_up, i8::MAX as u8) as i8;
+            for step_level in (level.saturating_sub(step)..=level.saturating_add(step)).rev() {
+                let (valid, new_level) = self.is_span_valid_at(capabilities, step_level, span_tiles.clone());
+                if valid {
+                    return (valid, new_level);
+                }
+            }
+        }
+        if capabilities.can_fly {
+            let clear = span_tiles.clone().all(|tile| match tile.get_level_range() {
+                None => true,
+                Some((_, max_level)) => max_level <= level
+            });
+            if clear {
+                return (true, level);
+            }
+        }
+        (false, level)
+    }
+
+    fn is_span_valid_at<'a, T>(&self, capabilities: &Capabilities, level: i8, span_tiles: T) -> (bool, i8)
+        where T: Iterator<Item = &'a MapTile> + Clone {
+        if !capabilities.can_swim && span_tiles.clone().any(|tile| tile.is_water_at(level)) {
+            return (false, level);
+        }
+        if capabilities.ignores_down_levels && span_tiles.clone().any(|tile| tile.get_down_level(&level).is_some()) {
+            return (false, level);
+        }
+        let mut tile_count = 0;
         let mut same_level_count = 0;
-        let mut special_levels = vec![];
-        for tile in span_tiles.iter() {
+        let mut special_count = 0;
+        let mut special_range: Option<(i8, i8)> = None;
+        for tile in span_tiles {
+            tile_count += 1;
             let (inc, level) = tile.get_validity_of(level);
-            same_level_count += inc;
+            same_level_count += inc as usize;
             if let Some(v) = level {
-                special_levels.push(v)
+                if !capabilities.can_use_stairs {
+                    return (false, v);
+                }
+                special_count += 1;
+                special_range = Some(special_range.map_or((v, v), |(lo, hi)| (min(lo, v), max(hi, v))));
             }
         }
-        if same_level_count as usize == span_tiles.len() {
+        if same_level_count == tile_count {
             return (true, level);
         }
-        if special_levels.len() == span_tiles.len() {
-            let min_special = special_levels.iter().min().unwrap();
-            let max_special = special_levels.iter().max().unwrap();
-            if (max_special - min_special) < 2 {
-                return if is_odd(min_special) {
-                    (true, *max_special)
+        if special_count == tile_count {
+            let (min_special, max_special) = special_range.unwrap();
+            if (max_special as i16 - min_special as i16) < 2 {
+                return if is_odd(&min_special) {
+                    (true, max_special)
                 }
                 else {
-                    (true, *min_special)
+                    (true, min_special)
                 }
             }
         }
         (false, level)
     }
 
-    fn is_shuffle_valid(&self, level: i8, stripes: &[Vec<&MapTile>], shuffle: Shuffle) -> (bool, i8, i8) {
-        let stripe = stripes.get(shuffle.index1).unwrap();
-        let (valid, new_level) = self.is_span_valid(level, stripe);
-        if valid {
-            return (valid, new_level, shuffle.shuffle1)
+    // a stripe is open if its tiles are valid, no obstacle sits in the part of the new rect it
+    // covers, and the step (rect, new rect) doesn't cross a closed side within it
+    fn is_stripe_open(&self, mover: &Mover, level: i8, step: (&Rect, &Rect), index: usize, vertical: bool) -> (bool, i8) {
+        let (rect, new_rect) = step;
+        let stripe = self.get_stripe_tiles(new_rect, index, vertical);
+        let (valid, new_level) = self.is_tiles_valid_for(&mover.capabilities, level, stripe);
+        if !valid {
+            return (valid, new_level);
+        }
+        let new_stripe_rect = self.get_stripe_rect(new_rect, index, vertical);
+        if self.is_blocked(mover, &new_stripe_rect, new_level) {
+            return (false, new_level);
+        }
+        let stripe_rect = self.get_stripe_rect(rect, index, vertical);
+        (self.is_crossing_valid(level, new_level, &stripe_rect, &new_stripe_rect), new_level)
+    }
+
+    fn is_shuffle_valid(&self, mover: &Mover, level: i8, step: (&Rect, &Rect), vertical: bool, shuffle: Shuffle) -> (bool, i8, i8) {
+        let max_overlap = min(mover.config.max_overlap, i32::MAX as u32) as i32;
+        if shuffle.overlap1 <= max_overlap {
+            let (valid, new_level) = self.is_stripe_open(mover, level, step, shuffle.index1, vertical);
+            if valid {
+                return (valid, new_level, shuffle.shuffle1)
+            }
         }
-        let stripe = stripes.get(shuffle.index2).unwrap();
-        let (valid, new_level) = self.is_span_valid(level, stripe);
-        return (valid, new_level, shuffle.shuffle2)
+        if shuffle.overlap2 <= max_overlap {
+            let (valid, new_level) = self.is_stripe_open(mover, level, step, shuffle.index2, vertical);
+            if valid {
+                return (valid, new_level, shuffle.shuffle2)
+            }
+        }
+        (false, level, 0)
     }
 
-    fn is_stripe_valid(&self, level: i8, stripes: &[Vec<&MapTile>], min: i32, max: i32) -> (bool, i8, i8) {
-        if stripes.len() < 2 {
-            return (false, level, 0);
+    fn is_stripe_valid(&self, mover: &Mover, level: i8, base_rect: &Rect, new_base_rect: &Rect, vertical: bool) -> Result<(i8, i8), FallbackFailure> {
+        if self.get_stripe_count(new_base_rect, vertical) < 2 {
+            return Err(FallbackFailure::SingleStripe);
+        }
+        let shuffle = self.get_shuffle(mover, base_rect, vertical);
+        let max_overlap = min(mover.config.max_overlap, i32::MAX as u32) as i32;
+        if shuffle.overlap1 > max_overlap && shuffle.overlap2 > max_overlap {
+            return Err(FallbackFailure::Overlap);
         }
+        match self.is_shuffle_valid(mover, level, (base_rect, new_base_rect), vertical, shuffle) {
+            (true, new_level, shuffle) => Ok((new_level, shuffle)),
+            _ => Err(FallbackFailure::Blocked)
+        }
+    }
+
+    fn get_shuffle(&self, mover: &Mover, base_rect: &Rect, vertical: bool) -> Shuffle {
+        let (min, max) = if vertical {
+            (base_rect.left, base_rect.right)
+        }
+        else {
+            (base_rect.top, base_rect.bottom)
+        };
         let min_diff = self.tile_size as i32 - (min % self.tile_size  as i32);
         let max_diff = max % self.tile_size as i32;
-        self.is_shuffle_valid(level, stripes, Shuffle::get_shuffle(min_diff, max_diff))
+        Shuffle::get_shuffle(min_diff, max_diff, mover.config.nudge())
     }
 
-    fn is_vertical_valid(&self, level: i8, base_rect: &Rect, verticals: &[Vec<&MapTile>]) -> (i8, Option<Rect>) {
-        let (valid, new_level, shuffle) = self.is_stripe_valid(level, verticals, base_rect.left, base_rect.right);
-        if valid {
-            return (new_level, Some(base_rect.move_rect(shuffle, 0)))
+    fn is_vertical_valid(&self, mover: &Mover, level: i8, base_rect: &Rect, new_base_rect: &Rect) -> Result<(i8, Rect), FallbackFailure> {
+        let (new_level, shuffle) = self.is_stripe_valid(mover, level, base_rect, new_base_rect, true)?;
+        let shuffled_rect = base_rect.move_rect(shuffle, 0);
+        match self.get_rejection(mover, true, (level, new_level), base_rect, &shuffled_rect) {
+            None => Ok((new_level, shuffled_rect)),
+            Some(_) => Err(FallbackFailure::Blocked)
         }
-        (0, None)
     }
 
-    fn is_horizontal_valid(&self, level: i8, base_rect: &Rect, horizontals: &[Vec<&MapTile>]) -> (i8, Option<Rect>) {
-        let (valid, new_level, shuffle) = self.is_stripe_valid(level, horizontals, base_rect.top, base_rect.bottom);
-        if valid {
-            return (new_level, Some(base_rect.move_rect(0, shuffle)))
+    fn is_horizontal_valid(&self, mover: &Mover, level: i8, base_rect: &Rect, new_base_rect: &Rect) -> Result<(i8, Rect), FallbackFailure> {
+        let (new_level, shuffle) = self.is_stripe_valid(mover, level, base_rect, new_base_rect, false)?;
+        let shuffled_rect = base_rect.move_rect(0, shuffle);
+        match self.get_rejection(mover, true, (level, new_level), base_rect, &shuffled_rect) {
+            None => Ok((new_level, shuffled_rect)),
+            Some(_) => Err(FallbackFailure::Blocked)
         }
-        (0, None)
     }
 
-    fn is_slide_valid(&self, mx: i8, my: i8, level: i8, base_rect: &Rect) -> (i8, Option<Rect>) {
-        let mx_base_rect = base_rect.move_rect(mx, 0);
-        let (valid, new_level) = self.is_move_valid(level, mx_base_rect);
-        if valid {
-            return (new_level, Some(mx_base_rect));
+    // tries each axis of a diagonal move in turn, noting how each one went in the trace
+    fn is_slide_valid(&self, mover: &Mover, mx: i8, my: i8, level: i8, base_rect: &Rect, mut trace: Option<&mut MoveTrace>) -> (i8, Option<Rect>) {
+        let mut slides = [(mx, 0), (0, my)];
+        if mover.config.prefer_free_axis
+            && self.get_free_space(mover, level, base_rect, 0, my) > self.get_free_space(mover, level, base_rect, mx, 0) {
+            slides.reverse();
         }
-        let my_base_rect = base_rect.move_rect(0, my);
-        let (valid, new_level) = self.is_move_valid(level, my_base_rect);
-        if valid {
-            return (new_level, Some(my_base_rect));
+        for (smx, smy) in slides {
+            let slide_rect = base_rect.move_rect(smx, smy);
+            let (valid, new_level) = self.is_tiles_valid_for(&mover.capabilities, level, self.get_span_tiles(&slide_rect));
+            let rejection = self.get_rejection(mover, valid, (level, new_level), base_rect, &slide_rect);
+            if let Some(trace) = trace.as_deref_mut() {
+                let blocking_tile = match rejection {
+                    Some(Rejection::Tiles) => self.get_tile_checks(level, &slide_rect).iter()
+                        .find(|check| check.is_blocking())
+                        .map(|check| (check.as_tuple().0, check.as_tuple().1)),
+                    _ => None
+                };
+                let failure = rejection.map(|_| FallbackFailure::Blocked);
+                trace.attempt(FallbackAttempt::new(Fallback::Slide, failure).with_step(smx, smy).with_rejection(rejection, blocking_tile));
+            }
+            if rejection.is_none() {
+                return (new_level, Some(slide_rect));
+            }
         }
         (0, None)
     }
 
-    fn get_sprite_masks(&self, rect: Rect, z: i32, level: i8, upright: bool) -> Vec<TileMasks> {
+    // number of valid steps that can be made in a row along one axis, up to a tile's worth
+    fn get_free_space(&self, mover: &Mover, level: i8, base_rect: &Rect, mx: i8, my: i8) -> u32 {
+        let steps = self.tile_size / max(max(mx.unsigned_abs(), my.unsigned_abs()), 1) as u32;
+        let mut rect = *base_rect;
+        let mut free_space = 0;
+        while free_space < steps {
+            let next_rect = rect.move_rect(mx, my);
+            if !self.is_step_valid_for(mover, level, rect, next_rect).0 {
+                break;
+            }
+            rect = next_rect;
+            free_space += 1;
+        }
+        free_space
+    }
+
+    pub fn get_sprite_masks(&self, rect: Rect, z: i32, level: i8, upright: bool) -> Vec<TileMasks> {
         let sprite_tiles = self.get_span_tiles_with_position(&rect);
 //        log!("sprite_tiles: {:?}", sprite_tiles);
+        // climbers are in front of whatever the ladder is fixed to
+        let climbing = self.get_ladder(&rect).is_some_and(|ladder| ladder.is_climbing(level));
         let mut sprite_masks = vec![];
-        sprite_tiles.into_iter().for_each(|(tx, ty, map_tile)| {
+        sprite_tiles.for_each(|(tx, ty, map_tile)| {
+            if climbing && map_tile.ladder.is_some() {
+                return;
+            }
             let tile_masks = map_tile.get_masks(z, level, upright);
 //            log!("tile_masks: {} {} {:?}", tx, ty, tile_masks);
-            if let Some(masks) = tile_masks {
+            if let Some(depth) = map_tile.get_water_depth(level) {
+                let waterline = rect.bottom - depth as i32;
+                sprite_masks.push(TileMasks::with_waterline(tx, ty, tile_masks.unwrap_or_default(), waterline));
+            }
+            else if let Some(masks) = tile_masks {
 //                log!("masks: {:?}", masks);
                 sprite_masks.push(TileMasks::new(tx, ty, masks));
             }
@@ -636,72 +1953,67 @@ impl PlayMap {
         sprite_masks
     }
 
+    // the part of rect that falls within the nth column (vertical) or row of tiles it spans
+    fn get_stripe_rect(&self, rect: &Rect, index: usize, vertical: bool) -> Rect {
+        let ts = self.tile_size as i32;
+        let (tx1, ty1, _, _) = self.convert_rect(rect);
+        if vertical {
+            let left = (tx1 as i32 + index as i32) * ts;
+            Rect::from_edges(max(rect.left, left), rect.top, min(rect.right, left + ts), rect.bottom)
+        }
+        else {
+            let top = (ty1 as i32 + index as i32) * ts;
+            Rect::from_edges(rect.left, max(rect.top, top), rect.right, min(rect.bottom, top + ts))
+        }
+    }
+
     fn get_index(&self, x: u8, y: u8) -> usize {
         y as usize * self.cols as usize + x as usize
     }
 
     fn convert_rect(&self, rect: &Rect) -> (u8, u8, u8, u8) {
-        let (left, top) = rect.top_left();
-        let tx1 = max(0, left / self.tile_size as i32);
-        let ty1 = max(0, top / self.tile_size as i32);
-        let (right, bottom) = rect.bottom_right();
-        let tx2 = min((self.cols - 1) as i32, (right - 1) / self.tile_size as i32) + 1;
-        let ty2 = min((self.rows - 1) as i32, (bottom - 1) / self.tile_size as i32) + 1;
-//        log!("{} {} {} {}", tx1, ty1, tx2, ty2);
-        (tx1 as u8, ty1 as u8, tx2 as u8, ty2 as u8)
+        rect.to_tile_range(self.tile_size, self.cols, self.rows).as_tuple()
     }
 
-    fn get_span_tiles(&self, rect: &Rect) -> Vec<&MapTile> {
-        let (tx1, ty1, tx2, ty2) = self.convert_rect(rect);
-        let mut span_tiles = vec![];
-        for x in tx1..tx2 {
-            for y in ty1..ty2 {
-                if let Some(tile) = self.tiles.get(self.get_index(x, y)) {
-                    span_tiles.push(tile)
-                }
-            }
-        }
-        span_tiles
+    // Tiles under rect, a column at a time, read straight off the map. These are cloned
+    // rather than collected when a span needs checking more than once, as they run every
+    // move and most spans are only one to four tiles.
+    fn get_span_tiles(&self, rect: &Rect) -> impl Iterator<Item = &MapTile> + Clone + '_ {
+        self.get_span_tiles_with_position(rect).map(|(_, _, tile)| tile)
+    }
+
+    fn get_span_tiles_with_position(&self, rect: &Rect) -> impl Iterator<Item = (u8, u8, &MapTile)> + Clone + '_ {
+        self.get_range_tiles(self.convert_rect(rect))
+    }
+
+    fn get_range_tiles(&self, tile_range: (u8, u8, u8, u8)) -> impl Iterator<Item = (u8, u8, &MapTile)> + Clone + '_ {
+        let (tx1, ty1, tx2, ty2) = tile_range;
+        (tx1..tx2).flat_map(move |x| (ty1..ty2).map(move |y| (x, y)))
+            .filter_map(move |(x, y)| self.tiles.get(self.get_index(x, y)).map(|tile| (x, y, tile)))
     }
 
-    fn get_span_tiles_with_position(&self, rect: &Rect) -> Vec<(u8, u8, &MapTile)> {
+    // the number of columns (vertical) or rows of tiles under rect
+    fn get_stripe_count(&self, rect: &Rect, vertical: bool) -> usize {
         let (tx1, ty1, tx2, ty2) = self.convert_rect(rect);
-        let mut span_tiles = vec![];
-        for x in tx1..tx2 {
-            for y in ty1..ty2 {
-                if let Some(tile) = self.tiles.get(self.get_index(x, y)) {
-                    span_tiles.push((x, y, tile))
-                }
-            }
+        if vertical {
+            (tx2 - tx1) as usize
+        }
+        else {
+            (ty2 - ty1) as usize
         }
-        span_tiles
     }
 
-    fn get_span_tiles_with_stripes(&self, rect: &Rect) -> (Vec<&MapTile>, Vec<Vec<&MapTile>>, Vec<Vec<&MapTile>>) {
+    // the tiles in the nth column (vertical) or row of tiles under rect
+    fn get_stripe_tiles(&self, rect: &Rect, index: usize, vertical: bool) -> impl Iterator<Item = &MapTile> + Clone + '_ {
         let (tx1, ty1, tx2, ty2) = self.convert_rect(rect);
-        let mut span_tiles = vec![];
-        let mut verticals = vec![];
-        for x in tx1..tx2 {
-            let mut vertical = vec![];
-            for y in ty1..ty2 {
-                if let Some(tile) = self.tiles.get(self.get_index(x, y)) {
-                    span_tiles.push(tile);
-                    vertical.push(tile);
-                }
-            }
-            verticals.push(vertical);
+        let tile_range = if vertical {
+            let x = tx1 + index as u8;
+            (x, ty1, x + 1, ty2)
         }
-        // TODO: combine this loop into the one above?
-        let mut horizontals = vec![];
-        for y in ty1..ty2 {
-            let mut horizontal = vec![];
-            for x in tx1..tx2 {
-                if let Some(tile) = self.tiles.get(self.get_index(x, y)) {
-                    horizontal.push(tile);
-                }
-            }
-            horizontals.push(horizontal);
-        }
-        (span_tiles, verticals, horizontals)
+        else {
+            let y = ty1 + index as u8;
+            (tx1, y, tx2, y + 1)
+        };
+        self.get_range_tiles(tile_range).map(|(_, _, tile)| tile)
     }
 }
@@ -0,0 +1,288 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::{MapTileData, PlayMap, PlayMapData, StairEnd, StairFault};
+
+// tileSize in www/config.js
+pub const DEFAULT_TILE_SIZE: u32 = 32;
+
+// The map format saved by the editor (www/data/maps). Levels are strings in whole
+// levels, eg. "4", "S3.5" or "D6-4", and are doubled when converted to tile data.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapDef {
+    pub name: String,
+    pub rows: u32,
+    pub cols: u32,
+    pub map_tiles: Vec<MapTileDef>
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapTileDef {
+    pub xy: (i32, i32),
+    pub tiles: Vec<TileDef>,
+    #[serde(default)]
+    pub levels: Vec<String>
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TileDef {
+    pub tile_set: String,
+    pub tile: String,
+    #[serde(default)]
+    pub mask_level: Option<String>
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TileSetDef {
+    pub name: String,
+    pub tiles: Vec<TileSetTileDef>
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TileSetTileDef {
+    pub name: String
+}
+
+// a parsed level string, doubled as in MapTileData
+#[derive(Debug, PartialEq)]
+pub enum Level {
+    Default(i8),
+    Special(i8),
+    Down(i8, u8)
+}
+
+// same rules as parseLevel/parseLevelx2 in the JS, but strict about trailing junk
+pub fn parse_level(level_str: &str) -> Result<Level, String> {
+    if let Some(special) = level_str.strip_prefix('S') {
+        let level: f32 = special.parse()
+            .map_err(|_| format!("special level could not be parsed as a float: {}", level_str))?;
+        let doubled = if level.fract() == 0.0 {
+            level * 2.0
+        }
+        else {
+            level.floor() * 2.0 + 1.0
+        };
+        return to_i8(doubled as i64, level_str).map(Level::Special);
+    }
+    if let Some(down) = level_str.strip_prefix('D') {
+        let parts: Vec<&str> = down.split('-').collect();
+        if parts.len() != 2 {
+            return Err(format!("down level did not have a level and drop separated by '-': {}", level_str));
+        }
+        let level: i64 = parts[0].parse()
+            .map_err(|_| format!("level component could not be parsed as an int: {}", level_str))?;
+        let drop: i64 = parts[1].parse()
+            .map_err(|_| format!("drop component could not be parsed as an int: {}", level_str))?;
+        if drop < 1 {
+            return Err(format!("drop component was not greater than zero: {}", level_str));
+        }
+        let drop = u8::try_from(drop.saturating_mul(2)).map_err(|_| format!("drop is out of range: {}", level_str))?;
+        return Ok(Level::Down(to_i8(level.saturating_mul(2), level_str)?, drop));
+    }
+    let level: i64 = level_str.parse()
+        .map_err(|_| format!("unknown level: {}", level_str))?;
+    to_i8(level.saturating_mul(2), level_str).map(Level::Default)
+}
+
+// "V4" is a vertical mask at level 4, anything else is a flat one
+pub fn parse_mask_level(mask_level: &str) -> Result<(i8, bool), String> {
+    let (level_str, flat) = match mask_level.strip_prefix('V') {
+        Some(level_str) => (level_str, false),
+        None => (mask_level, true)
+    };
+    let level: i64 = level_str.parse()
+        .map_err(|_| format!("unknown mask level: {}", mask_level))?;
+    Ok((to_i8(level.saturating_mul(2), mask_level)?, flat))
+}
+
+fn to_i8(doubled: i64, level_str: &str) -> Result<i8, String> {
+    i8::try_from(doubled).map_err(|_| format!("level is out of range: {}", level_str))
+}
+
+// undoes the doubling for messages, so they match what's in the map file
+fn whole_level(level: i8) -> String {
+    if level % 2 == 0 {
+        format!("{}", level / 2)
+    }
+    else {
+        format!("{}.5", level.div_euclid(2))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MapProblem {
+    pub xy: Option<(i32, i32)>,
+    pub message: String
+}
+
+impl MapProblem {
+    pub fn new(xy: Option<(i32, i32)>, message: String) -> MapProblem {
+        MapProblem { xy, message }
+    }
+}
+
+impl fmt::Display for MapProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.xy {
+            Some((x, y)) => write!(f, "[{}, {}] {}", x, y, self.message),
+            None => write!(f, "{}", self.message)
+        }
+    }
+}
+
+impl MapDef {
+    // builds the tile data the same way asTileData does in the JS, skipping anything that
+    // doesn't parse; tiles outside the map are dropped
+    pub fn to_play_map_data(&self, tile_size: u32) -> PlayMapData {
+        let (rows, cols) = (self.rows.min(u8::MAX as u32), self.cols.min(u8::MAX as u32));
+        let mut tile_data: Vec<MapTileData> = (0..rows * cols).map(|_| MapTileData::empty()).collect();
+        for map_tile in self.map_tiles.iter() {
+            let (x, y) = map_tile.xy;
+            if x < 0 || y < 0 || x as u32 >= cols || y as u32 >= rows {
+                continue;
+            }
+            tile_data[(y as u32 * cols + x as u32) as usize] = map_tile.to_tile_data(y as u8);
+        }
+        PlayMapData::new(rows as u8, cols as u8, tile_data, tile_size)
+    }
+
+    // everything wrong with the map that can be found without playing it
+    pub fn check(&self, tile_sets: Option<&HashMap<String, TileSetDef>>) -> Vec<MapProblem> {
+        let mut problems = vec![];
+        let sized = self.check_structure(&mut problems);
+        for map_tile in self.map_tiles.iter() {
+            map_tile.check_levels(&mut problems);
+            if let Some(tile_sets) = tile_sets {
+                map_tile.check_layers(tile_sets, &mut problems);
+            }
+        }
+        if sized {
+            let play_map = PlayMap::from_data(self.to_play_map_data(DEFAULT_TILE_SIZE));
+            self.check_stairs(&play_map, &mut problems);
+            self.check_down_levels(&play_map, &mut problems);
+        }
+        problems
+    }
+
+    // false if the map size is unusable, in which case there's no point loading it
+    fn check_structure(&self, problems: &mut Vec<MapProblem>) -> bool {
+        if self.rows == 0 || self.cols == 0 || self.rows > u8::MAX as u32 || self.cols > u8::MAX as u32 {
+            problems.push(MapProblem::new(None, format!("map size {}x{} is not between 1x1 and 255x255", self.cols, self.rows)));
+            return false;
+        }
+        if self.map_tiles.len() as u32 > self.rows * self.cols {
+            problems.push(MapProblem::new(None, format!("{} map tiles for a {}x{} map", self.map_tiles.len(), self.cols, self.rows)));
+        }
+        let mut seen = HashSet::new();
+        for map_tile in self.map_tiles.iter() {
+            let (x, y) = map_tile.xy;
+            if x < 0 || y < 0 || x as u32 >= self.cols || y as u32 >= self.rows {
+                problems.push(MapProblem::new(Some(map_tile.xy), "map tile is outside the map".to_string()));
+            }
+            else if !seen.insert(map_tile.xy) {
+                problems.push(MapProblem::new(Some(map_tile.xy), "map tile is defined more than once".to_string()));
+            }
+        }
+        true
+    }
+
+    // stairs have to lead somewhere walkable at both ends
+    fn check_stairs(&self, play_map: &PlayMap, problems: &mut Vec<MapProblem>) {
+        for broken_stair in play_map.lint_stairs() {
+            let end = match broken_stair.end() {
+                StairEnd::Bottom => "bottom",
+                StairEnd::Top => "top"
+            };
+            let level = whole_level(broken_stair.level());
+            let message = match broken_stair.fault() {
+                StairFault::HalfLevel => format!("stairs {} ends on special level S{}", end, level),
+                StairFault::Unconnected => format!("stairs {} at S{} doesn't touch anything walkable at level {}", end, level, level)
+            };
+            for (x, y) in broken_stair.tiles() {
+                problems.push(MapProblem::new(Some((*x as i32, *y as i32)), message.clone()));
+            }
+        }
+    }
+
+    // falling drops a tile per level, so the tile that far below has to have the level landed on
+    fn check_down_levels(&self, play_map: &PlayMap, problems: &mut Vec<MapProblem>) {
+        for map_tile in self.map_tiles.iter() {
+            let (x, y) = map_tile.xy;
+            for level in map_tile.parsed_levels() {
+                if let Level::Down(down_level, drop) = level {
+                    let target = match i8::try_from(down_level as i16 - drop as i16) {
+                        Ok(target) => target,
+                        Err(_) => {
+                            problems.push(MapProblem::new(Some(map_tile.xy), format!("down level D{}-{} drops below the lowest level", whole_level(down_level), drop / 2)));
+                            continue;
+                        }
+                    };
+                    let landed = play_map.get_tile_at(x, y + drop as i32 / 2)
+                        .is_some_and(|tile| tile.get_validity_of(target).0 == 1);
+                    if !landed {
+                        problems.push(MapProblem::new(Some(map_tile.xy), format!("down level D{}-{} drops to level {} which doesn't exist below", whole_level(down_level), drop / 2, whole_level(target))));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl MapTileDef {
+    fn parsed_levels(&self) -> Vec<Level> {
+        self.levels.iter().filter_map(|level_str| parse_level(level_str).ok()).collect()
+    }
+
+    fn to_tile_data(&self, y: u8) -> MapTileData {
+        let mut levels = vec![];
+        let mut down_levels = vec![];
+        let mut special_levels = vec![];
+        for level in self.parsed_levels() {
+            match level {
+                Level::Default(level) => levels.push(level),
+                Level::Special(level) => special_levels.push(level),
+                Level::Down(level, drop) => down_levels.push((level, drop))
+            }
+        }
+        let masks = self.tiles.iter().enumerate()
+            .filter_map(|(i, tile)| {
+                let (level, flat) = parse_mask_level(tile.mask_level.as_ref()?).ok()?;
+                Some((i as u8, level, flat, y))
+            })
+            .collect();
+        MapTileData::new(levels, down_levels, special_levels, masks)
+    }
+
+    fn check_levels(&self, problems: &mut Vec<MapProblem>) {
+        for level_str in self.levels.iter() {
+            if let Err(message) = parse_level(level_str) {
+                problems.push(MapProblem::new(Some(self.xy), message));
+            }
+        }
+        for tile in self.tiles.iter() {
+            if let Some(Err(message)) = tile.mask_level.as_ref().map(|mask_level| parse_mask_level(mask_level)) {
+                problems.push(MapProblem::new(Some(self.xy), message));
+            }
+        }
+    }
+
+    // masks are drawn from the tile layers, so every layer has to exist in its tile set
+    fn check_layers(&self, tile_sets: &HashMap<String, TileSetDef>, problems: &mut Vec<MapProblem>) {
+        for tile in self.tiles.iter() {
+            match tile_sets.get(&tile.tile_set) {
+                None => problems.push(MapProblem::new(Some(self.xy), format!("unknown tile set: {}", tile.tile_set))),
+                Some(tile_set) => {
+                    if !tile_set.tiles.iter().any(|t| t.name == tile.tile) {
+                        problems.push(MapProblem::new(Some(self.xy), format!("unknown tile: {}/{}", tile.tile_set, tile.tile)));
+                    }
+                }
+            }
+        }
+    }
+}
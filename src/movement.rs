@@ -0,0 +1,91 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+// Controls the corner assistance apply_move gives when a move is blocked: shuffling
+// around the corner of a tile, and sliding along a wall when moving diagonally.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy)]
+pub struct MovementConfig {
+    // pixels moved by each shuffle
+    pub nudge: u8,
+
+    // a shuffle is only allowed if the rect overlaps the blocked stripe by at most this many pixels
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = maxOverlap))]
+    pub max_overlap: u32,
+
+    // diagonal slides try the axis with more free space first, rather than x then y
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = preferFreeAxis))]
+    pub prefer_free_axis: bool,
+
+    // false disables shuffles and slides altogether, eg. for enemies
+    pub assist: bool
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl MovementConfig {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> MovementConfig {
+        MovementConfig { nudge: 2, max_overlap: u32::MAX, prefer_free_axis: false, assist: true }
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = noAssist))]
+    pub fn no_assist() -> MovementConfig {
+        MovementConfig { assist: false, ..MovementConfig::new() }
+    }
+}
+
+impl MovementConfig {
+    pub fn nudge(&self) -> i8 {
+        self.nudge.min(i8::MAX as u8) as i8
+    }
+}
+
+impl Default for MovementConfig {
+    fn default() -> MovementConfig {
+        MovementConfig::new()
+    }
+}
+
+// What a mover is able to do, consulted when checking whether the tiles under it are valid
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities {
+    // can pass over walls and lower tiles, keeping its level
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = canFly))]
+    pub can_fly: bool,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = canSwim))]
+    pub can_swim: bool,
+
+    // down levels don't count as somewhere to walk, so the mover keeps clear of holes
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = ignoresDownLevels))]
+    pub ignores_down_levels: bool,
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = canUseStairs))]
+    pub can_use_stairs: bool,
+
+    // difference in level the mover can step up, or equally down, without stairs, in the same
+    // doubled levels as everything else: 2 is one whole level
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = maxStepUp))]
+    pub max_step_up: u8
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Capabilities {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> Capabilities {
+        Capabilities {
+            can_fly: false,
+            can_swim: false,
+            ignores_down_levels: false,
+            can_use_stairs: true,
+            max_step_up: 0
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Capabilities {
+        Capabilities::new()
+    }
+}
@@ -0,0 +1,98 @@
+use std::cmp::min;
+
+use crate::Rect;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Waypoint {
+    x: i32,
+    y: i32,
+    level: i8,
+    wait: u32
+}
+
+impl Waypoint {
+    pub fn new(x: i32, y: i32, level: i8, wait: u32) -> Waypoint {
+        Waypoint { x, y, level, wait }
+    }
+}
+
+// A surface that travels between waypoints (top-left positions), moving at most
+// speed pixels per tick along each axis. The platform takes on the level of a
+// waypoint when it arrives there, and then waits for that waypoint's ticks.
+#[derive(Debug)]
+pub struct Platform {
+    rect: Rect,
+    level: i8,
+    speed: u8,
+    waypoints: Vec<Waypoint>,
+    next: usize,
+    waiting: u32,
+    last_rect: Rect,
+    last_level: i8
+}
+
+impl Platform {
+    pub fn new(rect: Rect, level: i8, speed: u8) -> Platform {
+        Platform {
+            rect,
+            level,
+            speed: min(speed, i8::MAX as u8),
+            waypoints: vec![],
+            next: 0,
+            waiting: 0,
+            last_rect: rect,
+            last_level: level
+        }
+    }
+
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    pub fn level(&self) -> i8 {
+        self.level
+    }
+
+    pub fn add_waypoint(&mut self, waypoint: Waypoint) {
+        self.waypoints.push(waypoint);
+    }
+
+    // true if the base rect was standing on this platform before the last tick
+    pub fn is_carrying(&self, level: i8, base_rect: &Rect) -> bool {
+        let (cx, cy) = base_rect.center();
+        self.last_level == level && self.last_rect.contains_point(cx, cy)
+    }
+
+    // the movement made by the last tick
+    pub fn carry(&self) -> (i8, i8, i8) {
+        let (mx, my) = self.last_rect.top_left_delta(&self.rect);
+        (mx, my, self.level)
+    }
+
+    pub fn tick(&mut self) {
+        self.last_rect = self.rect;
+        self.last_level = self.level;
+        if self.waypoints.is_empty() {
+            return;
+        }
+        if self.waiting > 0 {
+            self.waiting -= 1;
+            return;
+        }
+        let target = self.waypoints[self.next];
+        let (left, top) = self.rect.top_left();
+        let mx = Platform::step(left, target.x, self.speed);
+        let my = Platform::step(top, target.y, self.speed);
+        self.rect = self.rect.move_rect(mx, my);
+        if self.rect.top_left() == (target.x, target.y) {
+            self.level = target.level;
+            self.waiting = target.wait;
+            self.next = (self.next + 1) % self.waypoints.len();
+        }
+    }
+
+    fn step(from: i32, to: i32, speed: u8) -> i8 {
+        let distance = min((to - from).abs(), speed as i32);
+        ((to - from).signum() * distance) as i8
+    }
+}
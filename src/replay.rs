@@ -0,0 +1,221 @@
+use std::fmt;
+
+use crate::{PlayMap, Rect};
+
+// One call made to the map from the game, with its inputs and what it returned. Rects are
+// (left, top, width, height) and results are as_tuple() of MoveResult/MapEvent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayCall {
+    ApplyMove { mx: i8, my: i8, level: i8, rect: (i32, i32, u32, u32), result: (bool, u8, i8, i8, i8) },
+    AddLevelToTile { tx: u8, ty: u8, level: i8 },
+    RollbackTile { tx: u8, ty: u8 },
+    GetEvent { level: i8, rect: (i32, i32, u32, u32), event: Option<(u8, u8)> }
+}
+
+impl ReplayCall {
+    pub fn apply_move(mx: i8, my: i8, level: i8, rect: &Rect, result: (bool, u8, i8, i8, i8)) -> ReplayCall {
+        ReplayCall::ApplyMove { mx, my, level, rect: rect_tuple(rect), result }
+    }
+
+    pub fn get_event(level: i8, rect: &Rect, event: Option<(u8, u8)>) -> ReplayCall {
+        ReplayCall::GetEvent { level, rect: rect_tuple(rect), event }
+    }
+
+    // One line per call, the inputs then = and the outputs:
+    //   m <mx> <my> <level> <left> <top> <width> <height> = <valid> <deferral> <level> <mx> <my>
+    //   a <tx> <ty> <level>
+    //   r <tx> <ty>
+    //   e <level> <left> <top> <width> <height> = <event type> <value>, or = - for no event
+    pub fn parse(line: &str) -> Result<ReplayCall, String> {
+        let (inputs, outputs) = match line.split_once('=') {
+            Some((inputs, outputs)) => (inputs, Some(outputs.split_whitespace().collect::<Vec<&str>>())),
+            None => (line, None)
+        };
+        let inputs: Vec<&str> = inputs.split_whitespace().collect();
+        let call = match (inputs.first(), inputs.len(), outputs) {
+            (Some(&"m"), 8, Some(outputs)) if outputs.len() == 5 => ReplayCall::ApplyMove {
+                mx: parse_value(inputs[1])?,
+                my: parse_value(inputs[2])?,
+                level: parse_value(inputs[3])?,
+                rect: parse_rect(&inputs[4..])?,
+                result: (
+                    parse_value::<u8>(outputs[0])? == 1,
+                    parse_value(outputs[1])?,
+                    parse_value(outputs[2])?,
+                    parse_value(outputs[3])?,
+                    parse_value(outputs[4])?
+                )
+            },
+            (Some(&"a"), 4, None) => ReplayCall::AddLevelToTile {
+                tx: parse_value(inputs[1])?,
+                ty: parse_value(inputs[2])?,
+                level: parse_value(inputs[3])?
+            },
+            (Some(&"r"), 3, None) => ReplayCall::RollbackTile {
+                tx: parse_value(inputs[1])?,
+                ty: parse_value(inputs[2])?
+            },
+            (Some(&"e"), 6, Some(outputs)) => ReplayCall::GetEvent {
+                level: parse_value(inputs[1])?,
+                rect: parse_rect(&inputs[2..])?,
+                event: match outputs.as_slice() {
+                    ["-"] => None,
+                    [event_type, value] => Some((parse_value(event_type)?, parse_value(value)?)),
+                    _ => return Err(format!("unknown event: {}", line))
+                }
+            },
+            _ => return Err(format!("unknown call: {}", line))
+        };
+        Ok(call)
+    }
+
+    // makes the call against the map, returning it with the outputs the map gave this time
+    pub fn call(&self, play_map: &mut PlayMap) -> ReplayCall {
+        match *self {
+            ReplayCall::ApplyMove { mx, my, level, rect, .. } => {
+                let rect = to_rect(rect);
+                let result = play_map.apply_move(mx, my, level, rect).as_tuple();
+                ReplayCall::apply_move(mx, my, level, &rect, result)
+            }
+            ReplayCall::AddLevelToTile { tx, ty, level } => {
+                play_map.add_level_to_tile(tx, ty, level);
+                self.clone()
+            }
+            ReplayCall::RollbackTile { tx, ty } => {
+                play_map.rollback_tile(tx, ty);
+                self.clone()
+            }
+            ReplayCall::GetEvent { level, rect, .. } => {
+                let rect = to_rect(rect);
+                let event = play_map.get_event(level, rect).map(|event| event.as_tuple());
+                ReplayCall::get_event(level, &rect, event)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ReplayCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayCall::ApplyMove { mx, my, level, rect, result } => {
+                let (left, top, width, height) = rect;
+                let (valid, deferral, new_level, new_mx, new_my) = result;
+                write!(f, "m {} {} {} {} {} {} {} = {} {} {} {} {}",
+                       mx, my, level, left, top, width, height, *valid as u8, deferral, new_level, new_mx, new_my)
+            }
+            ReplayCall::AddLevelToTile { tx, ty, level } => write!(f, "a {} {} {}", tx, ty, level),
+            ReplayCall::RollbackTile { tx, ty } => write!(f, "r {} {}", tx, ty),
+            ReplayCall::GetEvent { level, rect, event } => {
+                let (left, top, width, height) = rect;
+                write!(f, "e {} {} {} {} {} = ", level, left, top, width, height)?;
+                match event {
+                    Some((event_type, value)) => write!(f, "{} {}", event_type, value),
+                    None => write!(f, "-")
+                }
+            }
+        }
+    }
+}
+
+fn rect_tuple(rect: &Rect) -> (i32, i32, u32, u32) {
+    let (left, top) = rect.top_left();
+    (left, top, rect.width(), rect.height())
+}
+
+fn to_rect(rect: (i32, i32, u32, u32)) -> Rect {
+    let (left, top, width, height) = rect;
+    Rect::new(left, top, width, height)
+}
+
+fn parse_value<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("unknown value: {}", value))
+}
+
+fn parse_rect(values: &[&str]) -> Result<(i32, i32, u32, u32), String> {
+    Ok((parse_value(values[0])?, parse_value(values[1])?, parse_value(values[2])?, parse_value(values[3])?))
+}
+
+// The first call in a replay that gave a different output, numbered from zero
+#[derive(Debug, PartialEq)]
+pub struct ReplayMismatch {
+    pub index: usize,
+    pub expected: ReplayCall,
+    pub actual: ReplayCall
+}
+
+impl fmt::Display for ReplayMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "call {} differs: expected `{}`, got `{}`", self.index, self.expected, self.actual)
+    }
+}
+
+// A log of calls made to a map, in a line per call text format, after a `t <tile size>`
+// line for the map they were made against. Blank lines and lines starting with # are
+// ignored when parsing.
+#[derive(Debug, Default, PartialEq)]
+pub struct Replay {
+    tile_size: Option<u32>,
+    calls: Vec<ReplayCall>
+}
+
+impl Replay {
+    pub fn new(tile_size: u32) -> Replay {
+        Replay { tile_size: Some(tile_size), calls: vec![] }
+    }
+
+    pub fn parse(text: &str) -> Result<Replay, String> {
+        let mut tile_size = None;
+        let mut calls = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(size) = line.strip_prefix("t ") {
+                if tile_size.is_some() || !calls.is_empty() {
+                    return Err(format!("line {}: the tile size has to come first", i + 1));
+                }
+                tile_size = Some(parse_value(size.trim()).map_err(|e| format!("line {}: {}", i + 1, e))?);
+                continue;
+            }
+            calls.push(ReplayCall::parse(line).map_err(|e| format!("line {}: {}", i + 1, e))?);
+        }
+        Ok(Replay { tile_size, calls })
+    }
+
+    // the tile size of the recorded map, or None if the replay doesn't say
+    pub fn tile_size(&self) -> Option<u32> {
+        self.tile_size
+    }
+
+    pub fn push(&mut self, call: ReplayCall) {
+        self.calls.push(call);
+    }
+
+    pub fn calls(&self) -> &[ReplayCall] {
+        &self.calls
+    }
+
+    // re-runs every call against the map, stopping at the first whose output differs
+    pub fn run(&self, play_map: &mut PlayMap) -> Result<(), ReplayMismatch> {
+        for (index, expected) in self.calls.iter().enumerate() {
+            let actual = expected.call(play_map);
+            if &actual != expected {
+                return Err(ReplayMismatch { index, expected: expected.clone(), actual });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(tile_size) = self.tile_size {
+            writeln!(f, "t {}", tile_size)?;
+        }
+        for call in self.calls.iter() {
+            writeln!(f, "{}", call)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{Deferral, EventType, PlayMap, Rect};
+
+// arrow keys as bit flags, as in PlayConfig.js
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy)]
+pub enum Key {
+    UP = 1,
+    DOWN = 2,
+    LEFT = 4,
+    RIGHT = 8,
+}
+
+// the base rect sits at the bottom of the sprite rect, as in Player._initBaseRect
+const BASE_RECT_HEIGHT: u32 = 18;
+const BASE_RECT_EXTENSION: i32 = 2;
+
+// pixels fallen per frame
+const FALL_UNIT: i32 = 4;
+
+fn get_move(keys: u8) -> Option<(i8, i8)> {
+    let up = keys & Key::UP as u8 != 0;
+    let down = keys & Key::DOWN as u8 != 0;
+    let left = keys & Key::LEFT as u8 != 0;
+    let right = keys & Key::RIGHT as u8 != 0;
+    if (up && down) || (left && right) {
+        return None;
+    }
+    let mx = if left { -2 } else if right { 2 } else { 0 };
+    let my = if up { -2 } else if down { 2 } else { 0 };
+    if mx == 0 && my == 0 {
+        None
+    }
+    else {
+        Some((mx, my))
+    }
+}
+
+// The player as Player.js sees it: a sprite rect for the map boundary, and a base rect
+// (its feet) for everything else.
+#[derive(Debug, Clone, Copy)]
+pub struct Actor {
+    rect: Rect,
+    base_rect: Rect,
+    level: i8
+}
+
+impl Actor {
+    pub fn new(level: i8, rect: Rect) -> Actor {
+        let base_rect_top = rect.bottom() + BASE_RECT_EXTENSION - BASE_RECT_HEIGHT as i32;
+        let base_rect = Rect::new(rect.left(), base_rect_top, rect.width(), BASE_RECT_HEIGHT);
+        Actor::with_base_rect(level, rect, base_rect)
+    }
+
+    pub fn with_base_rect(level: i8, rect: Rect, base_rect: Rect) -> Actor {
+        Actor { rect, base_rect, level }
+    }
+
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    pub fn base_rect(&self) -> Rect {
+        self.base_rect
+    }
+
+    pub fn level(&self) -> i8 {
+        self.level
+    }
+
+    fn move_by(&mut self, level: i8, mx: i8, my: i8) {
+        self.rect = self.rect.move_rect(mx, my);
+        self.base_rect = self.base_rect.move_rect(mx, my);
+        self.level = level;
+    }
+}
+
+// where the actor ended up after a frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub keys: u8,
+    pub x: i32,
+    pub y: i32,
+    pub level: i8,
+    pub falling: bool
+}
+
+impl Frame {
+    // base rect top left and level
+    pub fn as_tuple(&self) -> (i32, i32, i8) {
+        (self.x, self.y, self.level)
+    }
+}
+
+// Runs the map and the player for a number of frames without the Stage loop. Each tick
+// handles the keys held that frame like Player.handleInput, then checks for events like
+// Player.update. Levels are doubled, as everywhere else on the rust side.
+pub struct Simulation {
+    play_map: PlayMap,
+    actor: Actor,
+    keys: u8,
+    deferred: Option<(i8, i8, i8)>,
+    defer_diagonal: bool,
+    falling: i32
+}
+
+impl Simulation {
+    pub fn new(play_map: PlayMap, actor: Actor) -> Simulation {
+        Simulation { play_map, actor, keys: 0, deferred: None, defer_diagonal: true, falling: 0 }
+    }
+
+    pub fn play_map(&self) -> &PlayMap {
+        &self.play_map
+    }
+
+    pub fn play_map_mut(&mut self) -> &mut PlayMap {
+        &mut self.play_map
+    }
+
+    pub fn actor(&self) -> &Actor {
+        &self.actor
+    }
+
+    pub fn is_falling(&self) -> bool {
+        self.falling > 0
+    }
+
+    pub fn tick(&mut self, keys: u8) -> Frame {
+        self.handle_input(keys);
+        self.update();
+        let (x, y) = self.actor.base_rect.top_left();
+        Frame { keys, x, y, level: self.actor.level, falling: self.is_falling() }
+    }
+
+    // one tick per entry, returning the trace
+    pub fn run(&mut self, inputs: &[u8]) -> Vec<Frame> {
+        inputs.iter().map(|keys| self.tick(*keys)).collect()
+    }
+
+    fn handle_input(&mut self, keys: u8) {
+        if self.is_falling() {
+            return;
+        }
+        if keys == self.keys {
+            if let Some((level, mx, my)) = self.deferred.take() {
+                self.actor.move_by(level, mx, my);
+                return;
+            }
+        }
+        self.keys = keys;
+        if let Some((mx, my)) = get_move(keys) {
+            self.apply_move(mx, my);
+        }
+    }
+
+    fn apply_move(&mut self, mx: i8, my: i8) {
+        let map_rect = self.play_map.get_map_rect();
+        if !map_rect.contains_rect(&self.actor.rect.move_rect(mx, my)) {
+            return;
+        }
+        let result = self.play_map.apply_move(mx, my, self.actor.level, self.actor.base_rect);
+        if !result.valid {
+            return;
+        }
+        if result.deferral == Deferral::DEFAULT as u8 {
+            self.defer(result.level, result.mx, result.my);
+            return;
+        }
+        if result.deferral == Deferral::DIAGONAL as u8 && self.defer_diagonal {
+            self.defer_diagonal = false;
+            self.defer(result.level, result.mx, result.my);
+            return;
+        }
+        self.defer_diagonal = true;
+        self.actor.move_by(result.level, result.mx, result.my);
+    }
+
+    // the level changes straight away, the move happens next frame if the keys are the same
+    fn defer(&mut self, level: i8, mx: i8, my: i8) {
+        self.deferred = Some((level, mx, my));
+        self.actor.level = level;
+    }
+
+    fn update(&mut self) {
+        if self.is_falling() {
+            self.continue_falling();
+            return;
+        }
+        if let Some(event) = self.play_map.get_event(self.actor.level, self.actor.base_rect) {
+            let (event_type, value) = event.as_tuple();
+            if event_type == EventType::FALLING as u8 {
+                self.falling = (value / 2) as i32 * self.play_map.tile_size as i32;
+            }
+        }
+    }
+
+    // a level lost for every tile fallen
+    fn continue_falling(&mut self) {
+        let level = self.actor.level;
+        self.actor.move_by(level, 0, FALL_UNIT as i8);
+        if self.falling % self.play_map.tile_size as i32 == 0 {
+            self.actor.level -= 2;
+        }
+        self.falling -= FALL_UNIT;
+    }
+}
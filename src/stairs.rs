@@ -0,0 +1,170 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+use crate::{is_odd, PlayMap};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StairEnd {
+    Bottom,
+    Top
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StairFault {
+    // the run stops on an odd (half) level, so it can't lead anywhere walkable
+    HalfLevel,
+    // none of the tiles at the end touch a tile walkable at the end's level
+    Unconnected
+}
+
+// One end of a run of stairs that the player can't get on or off at. The tiles are
+// the run's tiles at the end's level.
+#[derive(Debug, PartialEq)]
+pub struct BrokenStair {
+    end: StairEnd,
+    level: i8,
+    fault: StairFault,
+    tiles: Vec<(u8, u8)>
+}
+
+impl BrokenStair {
+    pub fn new(end: StairEnd, level: i8, fault: StairFault, tiles: Vec<(u8, u8)>) -> BrokenStair {
+        BrokenStair { end, level, fault, tiles }
+    }
+
+    pub fn end(&self) -> StairEnd {
+        self.end
+    }
+
+    pub fn level(&self) -> i8 {
+        self.level
+    }
+
+    pub fn fault(&self) -> StairFault {
+        self.fault
+    }
+
+    pub fn tiles(&self) -> &[(u8, u8)] {
+        &self.tiles
+    }
+}
+
+impl fmt::Display for BrokenStair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let end = match self.end {
+            StairEnd::Bottom => "bottom",
+            StairEnd::Top => "top"
+        };
+        let tiles: Vec<String> = self.tiles.iter().map(|(x, y)| format!("[{}, {}]", x, y)).collect();
+        match self.fault {
+            StairFault::HalfLevel => write!(f, "stairs {} ends on special level {} at {}", end, self.level, tiles.join(" ")),
+            StairFault::Unconnected => write!(f, "stairs {} at {} doesn't touch anything walkable at level {}", end, tiles.join(" "), self.level)
+        }
+    }
+}
+
+impl PlayMap {
+    // Walks every run of stairs (adjacent special levels no more than half a level apart)
+    // and checks both ends: the lowest and highest levels of the run must be whole levels,
+    // and some tile at each must touch a tile that's walkable at that level.
+    pub fn lint_stairs(&self) -> Vec<BrokenStair> {
+        let mut broken = vec![];
+        let mut seen = HashSet::new();
+        for (x, y, level) in self.get_stair_tiles() {
+            if seen.contains(&(x, y, level)) {
+                continue;
+            }
+            let run = self.get_stair_run(x, y, level, &mut seen);
+            let bottom = run.iter().map(|(_, _, l)| *l).min().unwrap();
+            let top = run.iter().map(|(_, _, l)| *l).max().unwrap();
+            if let Some(broken_stair) = self.lint_stair_end(&run, StairEnd::Bottom, bottom) {
+                broken.push(broken_stair);
+            }
+            if top != bottom {
+                if let Some(broken_stair) = self.lint_stair_end(&run, StairEnd::Top, top) {
+                    broken.push(broken_stair);
+                }
+            }
+        }
+        broken
+    }
+
+    fn lint_stair_end(&self, run: &[(u8, u8, i8)], end: StairEnd, level: i8) -> Option<BrokenStair> {
+        let tiles: Vec<(u8, u8)> = run.iter()
+            .filter(|(_, _, l)| *l == level)
+            .map(|(x, y, _)| (*x, *y))
+            .collect();
+        if is_odd(&level) {
+            return Some(BrokenStair::new(end, level, StairFault::HalfLevel, tiles));
+        }
+        let connected = tiles.iter().any(|(x, y)| {
+            self.get_neighbours(*x, *y).iter().any(|(nx, ny)| {
+                self.get_tile_at(*nx as i32, *ny as i32)
+                    .is_some_and(|tile| tile.get_validity_of(level) == (1, None))
+            })
+        });
+        if connected {
+            None
+        }
+        else {
+            Some(BrokenStair::new(end, level, StairFault::Unconnected, tiles))
+        }
+    }
+
+    // flood fills from a stair tile, in the order the tiles were found
+    fn get_stair_run(&self, x: u8, y: u8, level: i8, seen: &mut HashSet<(u8, u8, i8)>) -> Vec<(u8, u8, i8)> {
+        let mut run = vec![(x, y, level)];
+        seen.insert((x, y, level));
+        let mut next = 0;
+        while next < run.len() {
+            let (x, y, level) = run[next];
+            for (nx, ny) in self.get_neighbours(x, y) {
+                for neighbour_level in self.get_tile_stair_levels(nx, ny) {
+                    if (neighbour_level as i16 - level as i16).abs() <= 1 && seen.insert((nx, ny, neighbour_level)) {
+                        run.push((nx, ny, neighbour_level));
+                    }
+                }
+            }
+            next += 1;
+        }
+        run
+    }
+
+    fn get_stair_tiles(&self) -> Vec<(u8, u8, i8)> {
+        let mut stair_tiles = vec![];
+        for y in 0..self.rows {
+            for x in 0..self.cols {
+                for level in self.get_tile_stair_levels(x, y) {
+                    stair_tiles.push((x, y, level));
+                }
+            }
+        }
+        stair_tiles
+    }
+
+    // the special levels as given in the tile data, before from_data expanded the odd ones,
+    // or none if the tile is missing
+    fn get_tile_stair_levels(&self, x: u8, y: u8) -> BTreeSet<i8> {
+        match self.tiles.get(self.get_index(x, y)) {
+            None => BTreeSet::new(),
+            Some(tile) => tile.special_levels.values().copied().collect()
+        }
+    }
+
+    fn get_neighbours(&self, x: u8, y: u8) -> Vec<(u8, u8)> {
+        let mut neighbours = vec![];
+        if y > 0 {
+            neighbours.push((x, y - 1));
+        }
+        if x + 1 < self.cols {
+            neighbours.push((x + 1, y));
+        }
+        if y + 1 < self.rows {
+            neighbours.push((x, y + 1));
+        }
+        if x > 0 {
+            neighbours.push((x - 1, y));
+        }
+        neighbours
+    }
+}
@@ -0,0 +1,67 @@
+//! Checks that the per-frame map queries don't touch the heap, by counting allocations.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+extern crate wasm_ulmo_map;
+use wasm_ulmo_map::{Capabilities, PlayMap, PlayMapData, Rect};
+
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(|allocations| allocations.get());
+    f();
+    ALLOCATIONS.with(|allocations| allocations.get()) - before
+}
+
+#[test]
+pub fn test_span_queries_dont_allocate() {
+    let mut play_map = PlayMap::from_data(PlayMapData::from_ascii("
+        [4]  [4]  [X]  [4]     [4]
+        [4]  [S4] [S3] [S2]    [2]
+        [X]  [4]  [X]  [D2-2]  [2]
+        [2]  [2]  [2]  [2]     [2]
+    ", 16).unwrap());
+    // a registered obstacle, so moves near it go through the entity lookup
+    play_map.insert_obstacle(1, 2, Rect::new(36, 52, 16, 8));
+    // rects of 1 to 4 tiles, walked over the whole map in every direction
+    let moves = [(2, 0), (-2, 0), (0, 2), (0, -2), (2, 2), (-2, -2), (2, -2), (-2, 2)];
+    let flying = Capabilities { can_fly: true, ..Capabilities::default() };
+    let stepping = Capabilities { max_step_up: 2, ..Capabilities::default() };
+    for level in (2..=4).step_by(2) {
+        for y in (-8..64).step_by(3) {
+            for x in (-8..80).step_by(3) {
+                let rect = Rect::new(x, y, 14, 12);
+                let allocations = count_allocations(|| {
+                    for (mx, my) in moves.iter() {
+                        play_map.apply_move(*mx, *my, level, rect);
+                        play_map.apply_move_with_capabilities(*mx, *my, level, rect, &flying);
+                        play_map.apply_move_with_capabilities(*mx, *my, level, rect, &stepping);
+                    }
+                    play_map.is_move_valid(level, rect);
+                    play_map.get_event(level, rect);
+                    play_map.get_jump(2, 0, 2, level, rect);
+                });
+                assert_eq!(allocations, 0, "level {} at ({}, {})", level, x, y);
+            }
+        }
+    }
+}
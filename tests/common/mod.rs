@@ -0,0 +1,55 @@
+// Fixtures shared by the native and browser test suites.
+#![allow(dead_code)]
+
+use wasm_ulmo_map::{PlayMap, Rect, MapTileData, PlayMapData};
+
+// [6]  [6]   [6]  <- level 6
+// [X] [D6-4] [X]  <- drop level + wall on either side
+// [X]  [X]   [X]  <- wall
+// [2]  [2]   [2]  <- level 2
+pub fn an_example_play_map_with_down_levels() -> PlayMap {
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![6]),           // 0
+        MapTileData::with_levels(vec![6]),           // 1
+        MapTileData::with_levels(vec![6]),           // 2
+        MapTileData::empty(),                        // 3
+        MapTileData::with_down_levels(vec![(6, 4)]), // 4
+        MapTileData::empty(),                        // 5
+        MapTileData::empty(),                        // 6
+        MapTileData::empty(),                        // 7
+        MapTileData::empty(),                        // 8
+        MapTileData::with_levels(vec![2]),           // 9
+        MapTileData::with_levels(vec![2]),           // 10
+        MapTileData::with_levels(vec![2]),           // 11
+    ];
+
+    PlayMap::from_data(PlayMapData::new(4, 3, map_tiles, 16))
+}
+
+// [6]  [6]   [6]  <- level 6
+// [X] [D6-4] [X]  <- drop level + wall on either side
+// [X]  [X]   [X]  <- wall
+// [2]  [2]   [2]  <- level 2
+pub fn an_example_play_map_with_masks() -> PlayMap {
+    let map_tiles = vec![
+        MapTileData::empty(),                            // 0
+        MapTileData::empty(),                            // 1
+        MapTileData::empty(),                            // 2
+        MapTileData::with_masks(vec![(1, 4, true, 1)]),  // 3
+        MapTileData::with_masks(vec![(1, 4, true, 1)]),  // 4
+        MapTileData::with_masks(vec![(1, 4, true, 1)]),  // 5
+        MapTileData::with_masks(vec![(0, 2, false, 2)]), // 6
+        MapTileData::with_masks(vec![(0, 2, false, 2)]), // 7
+        MapTileData::with_masks(vec![(0, 2, false, 2)]), // 8
+        MapTileData::empty(),                            // 9
+        MapTileData::empty(),                            // 10
+        MapTileData::empty(),                            // 11
+    ];
+
+    PlayMap::from_data(PlayMapData::new(4, 3, map_tiles, 16))
+}
+
+pub fn get_z_index(rect: Rect, level: i32, tile_size: u8) -> i32 {
+    let (_, bottom) = rect.bottom_right();
+    bottom + level * tile_size as i32
+}
@@ -0,0 +1,1646 @@
+//! Test suite for the map logic, run natively with cargo test.
+
+#![allow(clippy::bool_assert_comparison, clippy::get_first)]
+
+extern crate serde_json;
+
+extern crate wasm_ulmo_map;
+use wasm_ulmo_map::{PlayMap, Rect, MapTileData, PlayMapData, TileMasks, Camera, TileRange, Mover, MovementConfig, Capabilities, Rejection, Fallback, FallbackFailure, MapDef, TileSetDef, Level, parse_level, parse_mask_level, BrokenStair, StairEnd, StairFault, Replay, ReplayCall, Simulation, Actor, Key};
+use std::collections::HashMap;
+
+mod common;
+use common::*;
+
+// [4] [S4] [4]  <- level 4
+// [X] [S3] [X]  <- top of steps + wall on either side
+// [2] [S3] [2]  <- bottom of steps + level 2 on either side
+// [2] [S2] [2]  <- level 2
+pub fn an_example_play_map() -> PlayMap {
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![4]),         // 0
+        MapTileData::with_special_levels(vec![4]), // 1
+        MapTileData::with_levels(vec![4]),         // 2
+        MapTileData::empty(),                      // 3
+        MapTileData::with_special_levels(vec![3]), // 4
+        MapTileData::empty(),                      // 5
+        MapTileData::with_levels(vec![2]),         // 6
+        MapTileData::with_special_levels(vec![3]), // 7
+        MapTileData::with_levels(vec![2]),         // 8
+        MapTileData::with_levels(vec![2]),         // 9
+        MapTileData::with_special_levels(vec![2]), // 10
+        MapTileData::with_levels(vec![2]),         // 11
+    ];
+
+    PlayMap::from_data(PlayMapData::new(4, 3, map_tiles, 16))
+}
+
+#[test]
+pub fn test_play_map_get_masks_spright_upright() {
+    let play_map = an_example_play_map_with_masks();
+
+    /*
+     * spans [] []
+     */
+    let sprite_rect = Rect::new(8, 2, 16, 8);
+    // sprite level 2
+    let sprite_z = get_z_index(sprite_rect, 2, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 2, true);
+    assert_eq!(masks.len(), 0);
+    // sprite level 4
+    let sprite_z = get_z_index(sprite_rect, 4, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 4, true);
+    assert_eq!(masks.len(), 0);
+
+    /*
+     * spans   []     []
+     *       [m4 1] [m4 1]
+     */
+    let sprite_rect = Rect::new(8, 12, 16, 8);
+    // sprite level 2
+    let sprite_z = get_z_index(sprite_rect, 2, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 2, true);
+    assert_eq!(masks.len(), 2);
+    let mask1 = masks.get(0).unwrap();
+    assert_eq!(mask1, &TileMasks::new(0, 1, vec![1]));
+    let mask2 = masks.get(1).unwrap();
+    assert_eq!(mask2, &TileMasks::new(1, 1, vec![1]));
+    // sprite level 4
+    let sprite_z = get_z_index(sprite_rect, 4, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 4, true);
+    assert_eq!(masks.len(), 0);
+
+    /*
+     * spans [m4 1] [m4 1]
+     *       [m2 0] [m2 0]
+     */
+    let sprite_rect = Rect::new(8, 28, 16, 8);
+    // sprite level 2
+    let sprite_z = get_z_index(sprite_rect, 2, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 2, true);
+    assert_eq!(masks.len(), 4);
+    let mask1 = masks.get(0).unwrap();
+    assert_eq!(mask1, &TileMasks::new(0, 1, vec![1]));
+    let mask2 = masks.get(1).unwrap();
+    assert_eq!(mask2, &TileMasks::new(0, 2, vec![0]));
+    let mask3 = masks.get(2).unwrap();
+    assert_eq!(mask3, &TileMasks::new(1, 1, vec![1]));
+    let mask4 = masks.get(3).unwrap();
+    assert_eq!(mask4, &TileMasks::new(1, 2, vec![0]));
+    // sprite level 4
+    let sprite_z = get_z_index(sprite_rect, 4, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 4, true);
+    assert_eq!(masks.len(), 0);
+
+    /*
+     * spans [m2 0] [m2 0]
+     *         []     []
+     */
+    let sprite_rect = Rect::new(8, 44, 16, 8);
+    // sprite level 2
+    let sprite_z = get_z_index(sprite_rect, 2, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 2, true);
+    assert_eq!(masks.len(), 0);
+    // sprite level 4
+    let sprite_z = get_z_index(sprite_rect, 4, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 4, true);
+    assert_eq!(masks.len(), 0);
+}
+
+#[test]
+pub fn test_play_map_get_masks_sprite_flat() {
+    let play_map = an_example_play_map_with_masks();
+
+    /*
+     * spans [] []
+     */
+    let sprite_rect = Rect::new(8, 2, 16, 8);
+    // sprite level 2
+    let sprite_z = get_z_index(sprite_rect, 2, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 2, false);
+    assert_eq!(masks.len(), 0);
+    // sprite level 4
+    let sprite_z = get_z_index(sprite_rect, 4, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 4, false);
+    assert_eq!(masks.len(), 0);
+
+    /*
+     * spans   []     []
+     *       [m4 1] [m4 1]
+     */
+    let sprite_rect = Rect::new(8, 12, 16, 8);
+    // sprite level 2
+    let sprite_z = get_z_index(sprite_rect, 2, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 2, false);
+    assert_eq!(masks.len(), 2);
+    let mask1 = masks.get(0).unwrap();
+    assert_eq!(mask1, &TileMasks::new(0, 1, vec![1]));
+    let mask2 = masks.get(1).unwrap();
+    assert_eq!(mask2, &TileMasks::new(1, 1, vec![1]));
+    // sprite level 4
+    let sprite_z = get_z_index(sprite_rect, 4, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 4, false);
+    assert_eq!(masks.len(), 0);
+
+    /*
+     * spans [m4 1] [m4 1]
+     *       [m2 0] [m2 0]
+     */
+    let sprite_rect = Rect::new(8, 28, 16, 8);
+    // sprite level 2
+    let sprite_z = get_z_index(sprite_rect, 2, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 2, false);
+    assert_eq!(masks.len(), 4);
+    let mask1 = masks.get(0).unwrap();
+    assert_eq!(mask1, &TileMasks::new(0, 1, vec![1]));
+    let mask2 = masks.get(1).unwrap();
+    assert_eq!(mask2, &TileMasks::new(0, 2, vec![0]));
+    let mask3 = masks.get(2).unwrap();
+    assert_eq!(mask3, &TileMasks::new(1, 1, vec![1]));
+    let mask4 = masks.get(3).unwrap();
+    assert_eq!(mask4, &TileMasks::new(1, 2, vec![0]));
+    // sprite level 4
+    let sprite_z = get_z_index(sprite_rect, 4, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 4, false);
+    assert_eq!(masks.len(), 0); // TODO: should be 0?
+
+    /*
+     * spans [m2 0] [m2 0]
+     *         []     []
+     */
+    let sprite_rect = Rect::new(8, 44, 16, 8);
+    // sprite level 2
+    let sprite_z = get_z_index(sprite_rect, 2, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 2, false);
+    assert_eq!(masks.len(), 2);
+    let mask1 = masks.get(0).unwrap();
+    assert_eq!(mask1, &TileMasks::new(0, 2, vec![0]));
+    let mask2 = masks.get(1).unwrap();
+    assert_eq!(mask2, &TileMasks::new(1, 2, vec![0]));
+    // sprite level 4
+    let sprite_z = get_z_index(sprite_rect, 4, 16);
+    let masks = play_map.get_sprite_masks(sprite_rect, sprite_z, 4, false);
+    assert_eq!(masks.len(), 0);
+}
+
+#[test]
+pub fn test_play_map_apply_move_valid() {
+    let play_map = an_example_play_map();
+
+    /*
+     * spans [4] [S4]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 4, Rect::new(4, 2, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 4);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+
+    /*
+     * spans [2] [S2]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 2, Rect::new(4, 50, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 2);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+
+    /*
+     * spans [S4]
+     *       [S3]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 4, Rect::new(20, 2, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 4);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 3, Rect::new(20, 2, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 4);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+
+    /*
+     * spans [S3]
+     *       [S3]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 4, Rect::new(20, 20, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 3);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 3, Rect::new(20, 20, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 3);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 2, Rect::new(20, 20, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 3);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+
+    /*
+     * spans [S3]
+     *       [S2]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 3, Rect::new(20, 36, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 2);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 2, Rect::new(20, 36, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 2);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+}
+
+#[test]
+pub fn test_play_map_apply_move_shuffle() {
+    let play_map = an_example_play_map();
+
+    /*
+     * spans [X] [S3]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 3, Rect::new(4, 18, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 1);
+    assert_eq!(level, 3);
+    assert_eq!(mx, 2);
+    assert_eq!(my, 0);
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 2, Rect::new(4, 18, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 1);
+    assert_eq!(level, 3);
+    assert_eq!(mx, 2);
+    assert_eq!(my, 0);
+    /*
+     * spans [4] [S4]
+     *       [X] [S3]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(2, 0, 4, Rect::new(0, 12, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 1);
+    assert_eq!(level, 4);
+    assert_eq!(mx, 0);
+    assert_eq!(my, -2);
+
+    /*
+     * spans [2] [S3]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 3, Rect::new(4, 34, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 1);
+    assert_eq!(level, 3);
+    assert_eq!(mx, 2);
+    assert_eq!(my, 0);
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 2, Rect::new(4, 34, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 1);
+    assert_eq!(level, 2);
+    assert_eq!(mx, -2);
+    assert_eq!(my, 0);
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 0, 2, Rect::new(12, 34, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 1);
+    assert_eq!(level, 3);
+    assert_eq!(mx, 2);
+    assert_eq!(my, 0);
+    /*
+     * spans [2] [S3]
+     *       [2] [S2]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(2, 0, 2, Rect::new(0, 44, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 1);
+    assert_eq!(level, 2);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 2);
+}
+
+#[test]
+pub fn test_play_map_apply_move_slide() {
+    let play_map = an_example_play_map();
+
+    /*
+     * spans [4] [S4]
+     *       [X] [S3]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(2, 2, 4, Rect::new(12, 0, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 1);
+    assert_eq!(level, 4);
+    assert_eq!(mx, 2);
+    assert_eq!(my, 0);
+    let (valid, defer, level, mx, my) = play_map.apply_move(-2, 2, 4, Rect::new(12, 0, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 1);
+    assert_eq!(level, 4);
+    assert_eq!(mx, -2);
+    assert_eq!(my, 0);
+
+    /*
+     * spans [2] [S3]
+     *       [2] [S2]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(2, 2, 2, Rect::new(0, 44, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 1);
+    assert_eq!(level, 2);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 2);
+    let (valid, defer, level, mx, my) = play_map.apply_move(2, -2, 2, Rect::new(0, 44, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 1);
+    assert_eq!(level, 2);
+    assert_eq!(mx, 0);
+    assert_eq!(my, -2);
+}
+
+#[test]
+pub fn test_play_map_apply_move_invalid() {
+    let play_map = an_example_play_map();
+
+    /*
+     * spans [2] [S3]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(2, 0, 2, Rect::new(0, 34, 16, 8)).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 2);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+    let (valid, defer, level, mx, my) = play_map.apply_move(-2, 0, 3, Rect::new(16, 34, 16, 8)).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 3);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+
+    /*
+     * spans [4]
+     *       [X]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 2, 4, Rect::new(2, 0, 8, 16)).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 4);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+
+    /*
+     * spans [X]
+     *       [2]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, -2, 2, Rect::new(2, 32, 8, 16)).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 2);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+}
+
+#[test]
+pub fn test_play_map_add_and_rollback() {
+    let mut play_map = an_example_play_map();
+
+    /*
+     * spans [X]
+     *       [2]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, -2, 2, Rect::new(34, 32, 8, 16)).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 2);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+
+    // add level 2 to the [X] tile (move is now valid)
+    play_map.add_level_to_tile(2, 1, 2);
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, -2, 2, Rect::new(34, 32, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 2);
+    assert_eq!(mx, 0);
+    assert_eq!(my, -2);
+
+    // now roll it back (move invalid again)
+    play_map.rollback_tile(2, 1);
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, -2, 2, Rect::new(34, 32, 8, 16)).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 2);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 0);
+}
+
+#[test]
+pub fn test_play_map_get_event() {
+    let play_map = an_example_play_map_with_down_levels();
+
+    /*
+     * spans   [6]
+     *       [D6-4]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 2, 6, Rect::new(20, 4, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 6);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 2);
+
+    assert!(play_map.get_event(6, Rect::new(20, 6, 8, 16)).is_none());
+
+    /*
+     * spans [D6-4]
+     */
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 2, 6, Rect::new(20, 14, 8, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(defer, 0);
+    assert_eq!(level, 6);
+    assert_eq!(mx, 0);
+    assert_eq!(my, 2);
+
+    let event = play_map.get_event(6, Rect::new(20, 16, 8, 16)).unwrap();
+    let (event_type, value) = event.as_tuple();
+    assert_eq!(event_type, 1);
+    assert_eq!(value, 4);
+}
+
+#[test]
+pub fn test_rect_geometry() {
+    let rect = Rect::new(8, 4, 16, 8);
+    assert_eq!((rect.left(), rect.top(), rect.right(), rect.bottom()), (8, 4, 24, 12));
+    assert_eq!(rect.center(), (16, 8));
+
+    // touching edges do not intersect
+    assert!(rect.intersects_with(&Rect::new(20, 10, 8, 8)));
+    assert!(!rect.intersects_with(&Rect::new(24, 4, 8, 8)));
+    assert!(rect.intersection(&Rect::new(24, 4, 8, 8)).is_none());
+    let overlap = rect.intersection(&Rect::new(20, 10, 8, 8)).unwrap();
+    assert_eq!((overlap.top_left(), overlap.bottom_right()), ((20, 10), (24, 12)));
+
+    let union = rect.union(&Rect::new(20, 10, 8, 8));
+    assert_eq!((union.top_left(), union.bottom_right()), ((8, 4), (28, 18)));
+
+    assert!(rect.contains_point(8, 4));
+    assert!(!rect.contains_point(24, 4));
+    assert!(rect.contains_rect(&Rect::new(10, 6, 4, 4)));
+    assert!(!rect.contains_rect(&Rect::new(10, 6, 16, 4)));
+
+    let inflated = rect.inflate(2, 1);
+    assert_eq!((inflated.top_left(), inflated.bottom_right()), ((6, 3), (26, 13)));
+    let deflated = rect.deflate(10, 1);
+    assert_eq!((deflated.width(), deflated.height()), (0, 6));
+}
+
+#[test]
+pub fn test_rect_to_tile_range() {
+    // right/bottom edges are exclusive
+    assert_eq!(Rect::new(0, 0, 16, 16).to_tile_range(16, 3, 4), TileRange::new(0, 0, 1, 1));
+    assert_eq!(Rect::new(4, 18, 16, 8).to_tile_range(16, 3, 4), TileRange::new(0, 1, 2, 2));
+    // clipped to the map
+    assert_eq!(Rect::new(-8, -8, 16, 80).to_tile_range(16, 3, 4), TileRange::new(0, 0, 1, 4));
+    assert_eq!(Rect::new(40, 0, 16, 16).to_tile_range(16, 3, 4), TileRange::new(2, 0, 3, 1));
+    // entirely off the map spans nothing
+    assert_eq!(Rect::new(-16, 0, 16, 16).to_tile_range(16, 3, 4), TileRange::new(0, 0, 0, 1));
+    assert_eq!(Rect::new(64, 0, 16, 16).to_tile_range(16, 3, 4), TileRange::new(3, 0, 3, 1));
+    // no tiles without a tile size
+    assert_eq!(Rect::new(0, 0, 16, 16).to_tile_range(0, 3, 4), TileRange::new(0, 0, 0, 0));
+}
+
+#[test]
+pub fn test_play_map_off_the_map() {
+    let play_map = an_example_play_map();
+    assert_eq!(play_map.is_move_valid(2, Rect::new(-40, -40, 8, 8)), (false, 2));
+    assert_eq!(play_map.apply_move(-2, 0, 2, Rect::new(-40, 4, 8, 8)).as_tuple(), (false, 0, 2, 0, 0));
+    let flyer = Mover::anonymous().with_capabilities(Capabilities { can_fly: true, ..Capabilities::default() });
+    assert_eq!(play_map.apply_move_for(&flyer, -2, 0, 2, Rect::new(-40, 4, 8, 8)).valid, false);
+
+    // sprites stop at the edge rather than walking off
+    let mut play_map = an_example_play_map();
+    play_map.insert_sprite(1, 2, Rect::new(2, 52, 8, 8));
+    let (valid, _, _, mx, _) = play_map.apply_sprite_move(1, -2, 0).as_tuple();
+    assert_eq!((valid, mx), (true, -2));
+    play_map.insert_sprite(2, 2, Rect::new(-8, 52, 8, 8));
+    let (valid, _, _, _, _) = play_map.apply_sprite_move(2, -2, 0).as_tuple();
+    assert_eq!(valid, false);
+}
+
+#[test]
+pub fn test_camera_clamps_to_map() {
+    // 640 x 480 map, 320 x 240 view
+    let mut camera = Camera::new(320, 240, 640, 480);
+
+    let view_rect = camera.center_on(Rect::new(300, 200, 32, 32));
+    assert_eq!(view_rect.top_left(), (156, 96));
+
+    let view_rect = camera.center_on(Rect::new(0, 0, 32, 32));
+    assert_eq!(view_rect.top_left(), (0, 0));
+
+    let view_rect = camera.center_on(Rect::new(620, 460, 32, 32));
+    assert_eq!(view_rect.top_left(), (320, 240));
+    assert_eq!(view_rect.bottom_right(), (640, 480));
+}
+
+#[test]
+pub fn test_camera_centers_small_map() {
+    // 160 x 480 map, 320 x 240 view
+    let mut camera = Camera::new(320, 240, 160, 480);
+    let view_rect = camera.center_on(Rect::new(0, 200, 32, 32));
+    assert_eq!(view_rect.top_left(), (-80, 96));
+}
+
+#[test]
+pub fn test_camera_dead_zone_and_look_ahead() {
+    let mut camera = Camera::new(320, 240, 640, 480);
+    camera.set_dead_zone(64, 64);
+    camera.center_on(Rect::new(304, 224, 32, 32));
+
+    // target moves within the dead zone - camera stays put
+    let view_rect = camera.follow(Rect::new(324, 224, 32, 32), 2, 0);
+    assert_eq!(view_rect.top_left(), (160, 120));
+
+    // target leaves the dead zone - camera follows
+    let view_rect = camera.follow(Rect::new(340, 224, 32, 32), 2, 0);
+    assert_eq!(view_rect.top_left(), (164, 120));
+
+    // look ahead pushes the camera further in the direction of movement
+    camera.set_look_ahead(16);
+    let view_rect = camera.follow(Rect::new(340, 224, 32, 32), 2, 0);
+    assert_eq!(view_rect.top_left(), (180, 120));
+}
+
+#[test]
+pub fn test_camera_smoothing_and_shake() {
+    let mut camera = Camera::new(320, 240, 640, 480);
+    camera.center_on(Rect::new(144, 104, 32, 32));
+    camera.set_smoothing(0.5);
+    let view_rect = camera.follow(Rect::new(184, 104, 32, 32), 2, 0);
+    assert_eq!(view_rect.top_left(), (20, 0));
+    let view_rect = camera.follow(Rect::new(184, 104, 32, 32), 2, 0);
+    assert_eq!(view_rect.top_left(), (30, 0));
+
+    camera.set_smoothing(1.0);
+    camera.shake(4, 1);
+    let view_rect = camera.follow(Rect::new(304, 224, 32, 32), 0, 0);
+    let (left, top) = view_rect.top_left();
+    assert!((156..=164).contains(&left));
+    assert!((116..=124).contains(&top));
+    // shake is over
+    let view_rect = camera.follow(Rect::new(304, 224, 32, 32), 0, 0);
+    assert_eq!(view_rect.top_left(), (160, 120));
+}
+
+#[test]
+pub fn test_camera_visible_tiles() {
+    let mut camera = Camera::new(320, 240, 640, 480);
+    camera.center_on(Rect::new(0, 0, 32, 32));
+    assert_eq!(camera.visible_tiles(32), TileRange::new(0, 0, 10, 8));
+
+    camera.center_on(Rect::new(300, 200, 32, 32));
+    assert_eq!(camera.visible_tiles(32), TileRange::new(4, 3, 15, 11));
+
+    camera.center_on(Rect::new(620, 460, 32, 32));
+    assert_eq!(camera.visible_tiles(32), TileRange::new(10, 7, 20, 15));
+
+    // no tiles rather than a divide by zero
+    assert_eq!(camera.visible_tiles(0), TileRange::new(0, 0, 0, 0));
+}
+
+#[test]
+pub fn test_play_map_sprite_registry() {
+    let mut play_map = an_example_play_map();
+    play_map.insert_sprite(1, 4, Rect::new(0, 0, 16, 16));
+    play_map.insert_sprite(2, 4, Rect::new(20, 4, 16, 16));
+    play_map.insert_sprite(3, 2, Rect::new(20, 4, 16, 16));
+    assert_eq!(play_map.entities().len(), 3);
+
+    assert_eq!(play_map.get_sprites_overlapping(4, Rect::new(0, 0, 48, 16)), vec![1, 2]);
+    assert_eq!(play_map.get_sprites_overlapping(2, Rect::new(0, 0, 48, 16)), vec![3]);
+    assert!(play_map.get_sprites_overlapping(4, Rect::new(16, 0, 4, 16)).is_empty());
+
+    // moving right by 6 bumps sprite 2 but not sprite 3 (different level)
+    assert!(play_map.get_sprite_collisions(1, 4, 0).is_empty());
+    assert_eq!(play_map.get_sprite_collisions(1, 6, 0), vec![2]);
+
+    // moved sprites are found in their new tiles only
+    assert!(play_map.move_sprite(2, 4, Rect::new(16, 48, 16, 16)));
+    assert!(play_map.get_sprite_collisions(1, 6, 0).is_empty());
+    assert_eq!(play_map.get_sprites_overlapping(4, Rect::new(0, 40, 48, 16)), vec![2]);
+
+    assert!(play_map.remove_sprite(2));
+    assert!(!play_map.remove_sprite(2));
+    assert!(!play_map.move_sprite(2, 4, Rect::new(0, 0, 16, 16)));
+    assert!(play_map.get_sprites_overlapping(4, Rect::new(0, 40, 48, 16)).is_empty());
+}
+
+// [2] [2] [2]
+// [2] [2] [2]
+// [2] [2] [2]
+pub fn an_example_flat_play_map() -> PlayMap {
+    let map_tiles = (0..9).map(|_| MapTileData::with_levels(vec![2])).collect();
+    PlayMap::from_data(PlayMapData::new(3, 3, map_tiles, 16))
+}
+
+#[test]
+pub fn test_play_map_apply_move_obstacles() {
+    let mut play_map = an_example_flat_play_map();
+
+    // obstacle on a different level doesn't block
+    play_map.insert_obstacle(1, 4, Rect::new(0, 0, 16, 16));
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, -2, 2, Rect::new(8, 16, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!((defer, level, mx, my), (0, 2, 0, -2));
+
+    /*
+     * obstacle in [0, 0] - moving up shuffles right, diagonal slides along it
+     */
+    play_map.insert_obstacle(1, 2, Rect::new(0, 0, 16, 16));
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, -2, 2, Rect::new(8, 16, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!((defer, level, mx, my), (1, 2, 2, 0));
+    let (valid, defer, level, mx, my) = play_map.apply_move(2, -2, 2, Rect::new(8, 16, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!((defer, level, mx, my), (1, 2, 2, 0));
+
+    // moving straight into it is not valid
+    let (valid, defer, level, mx, my) = play_map.apply_move(-2, 0, 2, Rect::new(16, 4, 16, 8)).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!((defer, level, mx, my), (0, 2, 0, 0));
+    assert_eq!(play_map.is_move_valid(2, Rect::new(14, 4, 16, 8)), (false, 2));
+
+    // a sprite is not blocked by its own registration
+    let mover = Mover::sprite(1);
+    let (valid, _, _, mx, _) = play_map.apply_move_for(&mover, 2, 0, 2, Rect::new(0, 0, 16, 16)).as_tuple();
+    assert!(valid);
+    assert_eq!(mx, 2);
+
+    // opened doors no longer block
+    assert!(play_map.set_sprite_solid(1, false));
+    let (valid, defer, level, mx, my) = play_map.apply_move(-2, 0, 2, Rect::new(16, 4, 16, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!((defer, level, mx, my), (0, 2, -2, 0));
+}
+
+// [6]  [6]   [X]
+// [6]  [6]   [6]
+// [6] [D6-4] [6]  <- hole that can be filled
+pub fn an_example_play_map_with_hole() -> PlayMap {
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![6]),           // 0
+        MapTileData::with_levels(vec![6]),           // 1
+        MapTileData::empty(),                        // 2
+        MapTileData::with_levels(vec![6]),           // 3
+        MapTileData::with_levels(vec![6]),           // 4
+        MapTileData::with_levels(vec![6]),           // 5
+        MapTileData::with_levels(vec![6]),           // 6
+        MapTileData::with_down_levels(vec![(6, 4)]), // 7
+        MapTileData::with_levels(vec![6]),           // 8
+    ];
+
+    PlayMap::from_data(PlayMapData::new(3, 3, map_tiles, 16))
+}
+
+#[test]
+pub fn test_play_map_apply_push() {
+    let mut play_map = an_example_play_map_with_hole();
+    play_map.insert_pushable(1, 6, Rect::new(16, 0, 16, 16));
+
+    // nothing to push
+    assert!(play_map.apply_push(0, 2, 6, Rect::new(0, 4, 16, 8)).is_none());
+
+    // can't push into a wall, off the map or diagonally
+    let push = play_map.apply_push(2, 0, 6, Rect::new(0, 4, 16, 8)).unwrap();
+    assert_eq!(push.as_tuple(), (false, 1, false, 6, 16, 0));
+    let push = play_map.apply_push(0, -2, 6, Rect::new(16, 16, 16, 8)).unwrap();
+    assert_eq!(push.as_tuple(), (false, 1, false, 6, 16, 0));
+    let push = play_map.apply_push(2, -2, 6, Rect::new(0, 16, 16, 8)).unwrap();
+    assert_eq!(push.as_tuple(), (false, 1, false, 6, 16, 0));
+    // and the player can't walk through it
+    let (valid, _, _, _, _) = play_map.apply_move(2, 0, 6, Rect::new(0, 4, 16, 8)).as_tuple();
+    assert_eq!(valid, false);
+
+    // push it down towards the hole
+    for top in (2..32).step_by(2) {
+        let push = play_map.apply_push(0, 2, 6, Rect::new(16, top - 8, 16, 8)).unwrap();
+        assert_eq!(push.as_tuple(), (true, 1, false, 6, 16, top));
+    }
+    // player falls through the hole
+    assert!(play_map.get_event(6, Rect::new(16, 32, 16, 16)).is_some());
+
+    // the last push fills it and the block is gone
+    let push = play_map.apply_push(0, 2, 6, Rect::new(16, 24, 16, 8)).unwrap();
+    assert_eq!(push.as_tuple(), (true, 1, true, 6, 16, 32));
+    assert!(play_map.entities().get(1).is_none());
+    assert_eq!(play_map.is_move_valid(6, Rect::new(16, 32, 16, 16)), (true, 6));
+    assert!(play_map.get_event(6, Rect::new(16, 32, 16, 16)).is_none());
+}
+
+#[test]
+pub fn test_play_map_fill_survives_rollback() {
+    let mut play_map = an_example_play_map_with_hole();
+    play_map.insert_pushable(1, 6, Rect::new(16, 30, 16, 16));
+    // add a level to the hole tile, then fill the hole before rolling it back
+    play_map.add_level_to_tile(1, 2, 10);
+    assert_eq!(play_map.is_move_valid(10, Rect::new(16, 32, 16, 16)), (true, 10));
+    let push = play_map.apply_push(0, 2, 6, Rect::new(16, 22, 16, 8)).unwrap();
+    assert_eq!(push.as_tuple(), (true, 1, true, 6, 16, 32));
+    play_map.rollback_tile(1, 2);
+
+    // the added level is gone but the hole stays filled
+    assert_eq!(play_map.is_move_valid(10, Rect::new(16, 32, 16, 16)).0, false);
+    assert_eq!(play_map.is_move_valid(6, Rect::new(16, 32, 16, 16)), (true, 6));
+    assert!(play_map.get_event(6, Rect::new(16, 32, 16, 16)).is_none());
+}
+
+#[test]
+pub fn test_play_map_moving_platforms() {
+    // [2] [X] [X] [2]  <- raft crosses the gap
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![2]),
+        MapTileData::empty(),
+        MapTileData::empty(),
+        MapTileData::with_levels(vec![2]),
+    ];
+    let mut play_map = PlayMap::from_data(PlayMapData::new(1, 4, map_tiles, 16));
+    play_map.add_platform(1, 2, Rect::new(16, 0, 16, 16), 4);
+    play_map.add_platform_waypoint(1, 32, 0, 2, 0);
+    play_map.add_platform_waypoint(1, 16, 0, 2, 0);
+
+    assert_eq!(play_map.is_move_valid(2, Rect::new(16, 4, 16, 8)), (true, 2));
+    assert_eq!(play_map.is_move_valid(2, Rect::new(32, 4, 16, 8)), (false, 2));
+
+    // sprite on the raft is carried with it
+    play_map.tick_platforms();
+    let carry = play_map.get_platform_carry(2, Rect::new(18, 4, 8, 8)).unwrap();
+    assert_eq!(carry.as_tuple(), (1, 4, 0, 2));
+    assert!(play_map.get_platform_carry(2, Rect::new(2, 4, 8, 8)).is_none());
+    assert!(play_map.get_platform_carry(4, Rect::new(18, 4, 8, 8)).is_none());
+    assert_eq!(play_map.is_move_valid(2, Rect::new(20, 4, 16, 8)), (true, 2));
+
+    // once across, the gap it left behind is no longer walkable
+    for _ in 0..3 {
+        play_map.tick_platforms();
+    }
+    assert_eq!(play_map.is_move_valid(2, Rect::new(32, 4, 16, 8)), (true, 2));
+    assert_eq!(play_map.is_move_valid(2, Rect::new(16, 4, 16, 8)), (false, 2));
+    let (valid, _, _, _, _) = play_map.apply_move(-2, 0, 2, Rect::new(48, 4, 16, 8)).as_tuple();
+    assert!(valid);
+
+    // a lift changes level in place
+    play_map.add_platform(2, 2, Rect::new(0, 0, 16, 16), 4);
+    play_map.add_platform_waypoint(2, 0, 0, 4, 0);
+    play_map.tick_platforms();
+    let carry = play_map.get_platform_carry(2, Rect::new(4, 4, 8, 8)).unwrap();
+    assert_eq!(carry.as_tuple(), (2, 0, 0, 4));
+    assert_eq!(play_map.is_move_valid(4, Rect::new(0, 4, 16, 8)), (true, 4));
+
+    assert!(play_map.remove_platform(2));
+    assert_eq!(play_map.is_move_valid(4, Rect::new(0, 4, 16, 8)), (false, 4));
+}
+
+#[test]
+pub fn test_play_map_tile_modifiers() {
+    // [ice] [conveyor >] [slow 50%] [2]
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![2]).with_modifier((1, 0, 0)),
+        MapTileData::with_levels(vec![2]).with_modifier((2, 2, 0)),
+        MapTileData::with_levels(vec![2]).with_modifier((3, 50, 0)),
+        MapTileData::with_levels(vec![2]),
+    ];
+    let mut play_map = PlayMap::from_data(PlayMapData::new(1, 4, map_tiles, 16));
+
+    // ice keeps the sprite moving after the input stops
+    play_map.insert_sprite(1, 2, Rect::new(2, 4, 8, 8));
+    let result = play_map.apply_sprite_move(1, 2, 0);
+    assert_eq!(result.as_tuple(), (true, 0, 2, 2, 0));
+    assert_eq!((result.extra_mx, result.extra_my), (0, 0));
+    let result = play_map.apply_sprite_move(1, 0, 0);
+    assert_eq!(result.as_tuple(), (true, 0, 2, 2, 0));
+    assert_eq!((result.extra_mx, result.extra_my), (2, 0));
+    assert_eq!(play_map.entities().get(1).unwrap().rect().top_left(), (6, 4));
+
+    // ice momentum is lost on hitting something
+    play_map.insert_obstacle(5, 2, Rect::new(0, 0, 4, 16));
+    play_map.insert_sprite(4, 2, Rect::new(6, 4, 8, 8));
+    play_map.apply_sprite_move(4, -2, 0);
+    let (valid, _, _, _, _) = play_map.apply_sprite_move(4, 0, 0).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!(play_map.entities().get(4).unwrap().momentum(), (0, 0));
+
+    // conveyor pushes the sprite along
+    play_map.insert_sprite(2, 2, Rect::new(20, 4, 8, 8));
+    let result = play_map.apply_sprite_move(2, 0, 0);
+    assert_eq!(result.as_tuple(), (true, 0, 2, 2, 0));
+    assert_eq!((result.extra_mx, result.extra_my), (2, 0));
+    let result = play_map.apply_sprite_move(2, 2, 0);
+    assert_eq!(result.as_tuple(), (true, 0, 2, 4, 0));
+    assert_eq!((result.extra_mx, result.extra_my), (2, 0));
+
+    // slow tile halves the move, rounding away from zero
+    play_map.insert_sprite(3, 2, Rect::new(36, 4, 8, 8));
+    let result = play_map.apply_sprite_move(3, 4, 0);
+    assert_eq!(result.as_tuple(), (true, 0, 2, 2, 0));
+    assert_eq!((result.extra_mx, result.extra_my), (-2, 0));
+    let result = play_map.apply_sprite_move(3, -3, 0);
+    assert_eq!(result.as_tuple(), (true, 0, 2, -2, 0));
+    assert_eq!((result.extra_mx, result.extra_my), (1, 0));
+
+    // unknown sprite
+    let (valid, _, _, _, _) = play_map.apply_sprite_move(9, 2, 0).as_tuple();
+    assert_eq!(valid, false);
+}
+
+#[test]
+pub fn test_play_map_extreme_modifiers() {
+    // [conveyor >>] [slow -50%] [2] ... wide enough that nothing is clipped
+    let mut map_tiles: Vec<MapTileData> = (0..40).map(|_| MapTileData::with_levels(vec![2])).collect();
+    map_tiles[0] = MapTileData::with_levels(vec![2]).with_modifier((2, 100, 0));
+    map_tiles[1] = MapTileData::with_levels(vec![2]).with_modifier((3, -50, 0));
+    let mut play_map = PlayMap::from_data(PlayMapData::new(1, 40, map_tiles, 16));
+
+    // the conveyed move saturates rather than overflowing
+    play_map.insert_sprite(1, 2, Rect::new(2, 4, 8, 8));
+    let result = play_map.apply_sprite_move(1, 100, 0);
+    assert_eq!(result.as_tuple(), (true, 0, 2, 127, 0));
+    assert_eq!((result.extra_mx, result.extra_my), (100, 0));
+
+    // a slow tile outside 0 to 100 percent is ignored
+    play_map.insert_sprite(2, 2, Rect::new(20, 4, 8, 8));
+    let result = play_map.apply_sprite_move(2, 4, 0);
+    assert_eq!(result.as_tuple(), (true, 0, 2, 4, 0));
+    assert_eq!((result.extra_mx, result.extra_my), (0, 0));
+}
+
+#[test]
+pub fn test_play_map_one_way_tiles_and_edges() {
+    // [2|] [2 ] [2]   <- fence on the right of [0, 0]
+    // [2 ] [2v] [2]   <- ledge in [1, 1] only entered from the top
+    // [2 ] [2 ] [2]
+    let mut map_tiles: Vec<MapTileData> = (0..9).map(|_| MapTileData::with_levels(vec![2])).collect();
+    map_tiles[0] = MapTileData::with_levels(vec![2]).with_edges(2, 2);
+    map_tiles[4] = MapTileData::with_levels(vec![2]).with_entry_sides(2, 1);
+    let play_map = PlayMap::from_data(PlayMapData::new(3, 3, map_tiles, 16));
+
+    // hop down onto the ledge, but not back up
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, 2, 2, Rect::new(20, 8, 8, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!((defer, level, mx, my), (0, 2, 0, 2));
+    let (valid, _, _, _, _) = play_map.apply_move(0, -2, 2, Rect::new(20, 32, 8, 8)).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!(play_map.is_move_valid(2, Rect::new(20, 30, 8, 8)), (true, 2));
+    assert_eq!(play_map.is_step_valid(2, Rect::new(20, 32, 8, 8), Rect::new(20, 30, 8, 8)), (false, 2));
+    assert_eq!(play_map.is_step_valid(2, Rect::new(20, 16, 8, 8), Rect::new(20, 14, 8, 8)), (true, 2));
+
+    // straddling the ledge from below shuffles towards the open side
+    let (valid, defer, level, mx, my) = play_map.apply_move(0, -2, 2, Rect::new(12, 32, 8, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!((defer, level, mx, my), (1, 2, -2, 0));
+
+    // fence blocks in both directions
+    let (valid, _, _, _, _) = play_map.apply_move(2, 0, 2, Rect::new(8, 4, 8, 8)).as_tuple();
+    assert_eq!(valid, false);
+    let (valid, _, _, _, _) = play_map.apply_move(-2, 0, 2, Rect::new(16, 4, 8, 8)).as_tuple();
+    assert_eq!(valid, false);
+
+    // diagonal into the fence slides along it
+    let (valid, defer, level, mx, my) = play_map.apply_move(2, 2, 2, Rect::new(8, 4, 8, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!((defer, level, mx, my), (1, 2, 0, 2));
+
+    // edges only apply at their level
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![2, 4]).with_edges(4, 2),
+        MapTileData::with_levels(vec![2, 4]),
+    ];
+    let play_map = PlayMap::from_data(PlayMapData::new(1, 2, map_tiles, 16));
+    assert_eq!(play_map.is_step_valid(2, Rect::new(8, 4, 8, 8), Rect::new(10, 4, 8, 8)), (true, 2));
+    assert_eq!(play_map.is_step_valid(4, Rect::new(8, 4, 8, 8), Rect::new(10, 4, 8, 8)), (false, 4));
+}
+
+#[test]
+pub fn test_play_map_short_tile_list() {
+    // 2 x 2 map with only the top row of tiles
+    let map_tiles = vec![MapTileData::with_levels(vec![2]), MapTileData::with_levels(vec![2])];
+    let play_map = PlayMap::from_data(PlayMapData::new(2, 2, map_tiles, 16));
+
+    // the missing tiles are closed
+    let (valid, _, _, _, _) = play_map.apply_move(0, 2, 2, Rect::new(0, 8, 8, 8)).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!(play_map.apply_move(2, 0, 2, Rect::new(0, 0, 8, 8)).as_tuple(), (true, 0, 2, 2, 0));
+
+    // a ladder running down to the missing row ends there
+    let map_tiles = vec![MapTileData::empty().with_ladder(2, 6), MapTileData::empty().with_ladder(2, 6)];
+    let play_map = PlayMap::from_data(PlayMapData::new(3, 1, map_tiles, 16));
+    let (valid, _, _, _, _) = play_map.apply_move(0, 4, 4, Rect::new(4, 20, 8, 8)).as_tuple();
+    assert_eq!(valid, true);
+}
+
+#[test]
+pub fn test_play_map_get_jump() {
+    // [4] [X] [2] [6] [4] [2]
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![4]),
+        MapTileData::empty(),
+        MapTileData::with_levels(vec![2]),
+        MapTileData::with_levels(vec![6]),
+        MapTileData::with_levels(vec![4]),
+        MapTileData::with_levels(vec![2]),
+    ];
+    let play_map = PlayMap::from_data(PlayMapData::new(1, 6, map_tiles, 16));
+
+    // over the gap and down a level
+    let jump = play_map.get_jump(1, 0, 2, 4, Rect::new(4, 4, 8, 8));
+    assert_eq!(jump.as_tuple(), (true, 2, 36, 4));
+    let landing_rect = jump.landing_rect();
+    assert_eq!((landing_rect.width(), landing_rect.height()), (8, 8));
+
+    // over a wall one level higher, landing at the same level
+    assert_eq!(play_map.get_jump(1, 0, 4, 4, Rect::new(4, 4, 8, 8)).as_tuple(), (true, 4, 68, 4));
+
+    // can't land higher, clear a wall two levels higher, or leave the map
+    assert_eq!(play_map.get_jump(1, 0, 2, 2, Rect::new(36, 4, 8, 8)).as_tuple(), (false, 2, 36, 4));
+    assert_eq!(play_map.get_jump(1, 0, 3, 2, Rect::new(36, 4, 8, 8)).as_tuple(), (false, 2, 36, 4));
+    assert_eq!(play_map.get_jump(-1, 0, 1, 4, Rect::new(4, 4, 8, 8)).as_tuple(), (false, 4, 4, 4));
+    assert_eq!(play_map.get_jump(0, 0, 1, 4, Rect::new(4, 4, 8, 8)).as_tuple(), (false, 4, 4, 4));
+
+    // can't land in the gap
+    assert_eq!(play_map.get_jump(1, 0, 1, 4, Rect::new(4, 4, 8, 8)).as_tuple(), (false, 4, 4, 4));
+
+    // [2] [2] [8]
+    // [2] [2] [2]   <- diagonal jumps only need the tiles they pass over to be clear
+    // [8] [2] [2]
+    let mut map_tiles: Vec<MapTileData> = (0..9).map(|_| MapTileData::with_levels(vec![2])).collect();
+    map_tiles[2] = MapTileData::with_levels(vec![8]);
+    map_tiles[6] = MapTileData::with_levels(vec![8]);
+    let mut play_map = PlayMap::from_data(PlayMapData::new(3, 3, map_tiles, 16));
+    assert_eq!(play_map.get_jump(1, 1, 2, 2, Rect::new(4, 4, 8, 8)).as_tuple(), (true, 2, 36, 36));
+    assert_eq!(play_map.get_jump(-1, -1, 2, 2, Rect::new(36, 36, 8, 8)).as_tuple(), (true, 2, 4, 4));
+    play_map.add_level_to_tile(1, 1, 8);
+    assert_eq!(play_map.get_jump(1, 1, 2, 2, Rect::new(4, 4, 8, 8)).as_tuple(), (false, 2, 4, 4));
+}
+
+#[test]
+pub fn test_play_map_fixed_point_movement() {
+    let mut play_map = an_example_flat_play_map();
+
+    // half a pixel per move alternates between 0 and 1
+    play_map.insert_sprite(1, 2, Rect::new(8, 8, 8, 8));
+    let deltas: Vec<i8> = (0..4).map(|_| play_map.apply_sprite_move_fixed(1, 128, 0).mx).collect();
+    assert_eq!(deltas, vec![0, 1, 0, 1]);
+    assert_eq!(play_map.entities().get(1).unwrap().fixed_position(), (10 * 256, 8 * 256));
+
+    // diagonal speed keeps its remainder between moves
+    play_map.insert_sprite(2, 2, Rect::new(8, 8, 8, 8));
+    let deltas: Vec<(i8, i8)> = (0..4).map(|_| {
+        let result = play_map.apply_sprite_move_fixed(2, 181, -181);
+        (result.mx, result.my)
+    }).collect();
+    assert_eq!(deltas, vec![(0, 0), (1, -1), (1, -1), (0, 0)]);
+    assert_eq!(play_map.entities().get(2).unwrap().fixed_position(), (8 * 256 + 724, 8 * 256 - 724));
+
+    // being blocked drops the remainder
+    play_map.insert_obstacle(9, 2, Rect::new(17, 0, 8, 48));
+    play_map.insert_sprite(3, 2, Rect::new(8, 24, 8, 8));
+    assert_eq!(play_map.apply_sprite_move_fixed(3, 384, 0).as_tuple(), (true, 0, 2, 1, 0));
+    assert_eq!(play_map.apply_sprite_move_fixed(3, 128, 0).as_tuple(), (false, 0, 2, 0, 0));
+    assert_eq!(play_map.entities().get(3).unwrap().fixed_position(), (9 * 256, 24 * 256));
+
+    // unknown sprite
+    let (valid, _, _, _, _) = play_map.apply_sprite_move_fixed(4, 256, 0).as_tuple();
+    assert_eq!(valid, false);
+}
+
+#[test]
+pub fn test_play_map_movement_config() {
+    let mut play_map = an_example_flat_play_map();
+    play_map.insert_obstacle(1, 2, Rect::new(0, 0, 16, 16));
+
+    // default shuffles by 2
+    let result = play_map.apply_move_with_config(0, -2, 2, Rect::new(8, 16, 16, 8), &MovementConfig::new());
+    assert_eq!(result.as_tuple(), (true, 1, 2, 2, 0));
+
+    // bigger nudge
+    let config = MovementConfig { nudge: 4, ..MovementConfig::new() };
+    let result = play_map.apply_move_with_config(0, -2, 2, Rect::new(8, 16, 16, 8), &config);
+    assert_eq!(result.as_tuple(), (true, 1, 2, 4, 0));
+
+    // overlapping the blocked stripe by 8 is too much
+    let config = MovementConfig { max_overlap: 4, ..MovementConfig::new() };
+    let (valid, _, _, _, _) = play_map.apply_move_with_config(0, -2, 2, Rect::new(8, 16, 16, 8), &config).as_tuple();
+    assert_eq!(valid, false);
+    let (valid, _, _, _, _) = play_map.apply_move_with_config(0, -2, 2, Rect::new(12, 16, 16, 8), &config).as_tuple();
+    assert!(valid);
+
+    // no assistance at all
+    let (valid, _, _, _, _) = play_map.apply_move_with_config(0, -2, 2, Rect::new(8, 16, 16, 8), &MovementConfig::no_assist()).as_tuple();
+    assert_eq!(valid, false);
+    let (valid, _, _, _, _) = play_map.apply_move_with_config(2, -2, 2, Rect::new(8, 16, 16, 8), &MovementConfig::no_assist()).as_tuple();
+    assert_eq!(valid, false);
+
+    // set per sprite
+    play_map.insert_sprite(2, 2, Rect::new(8, 16, 16, 8));
+    assert!(play_map.set_sprite_movement_config(2, &MovementConfig::no_assist()));
+    let (valid, _, _, _, _) = play_map.apply_sprite_move(2, 0, -2).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!(play_map.set_sprite_movement_config(3, &MovementConfig::no_assist()), false);
+
+    // diagonal slide along the axis with more room
+    let mut play_map = an_example_flat_play_map();
+    play_map.insert_obstacle(1, 2, Rect::new(16, 14, 8, 9));
+    play_map.insert_obstacle(2, 2, Rect::new(22, 24, 4, 8));
+    let result = play_map.apply_move_with_config(2, -2, 2, Rect::new(8, 24, 8, 8), &MovementConfig::new());
+    assert_eq!(result.as_tuple(), (true, 1, 2, 2, 0));
+    let config = MovementConfig { prefer_free_axis: true, ..MovementConfig::new() };
+    let result = play_map.apply_move_with_config(2, -2, 2, Rect::new(8, 24, 8, 8), &config);
+    assert_eq!(result.as_tuple(), (true, 1, 2, 0, -2));
+}
+
+#[test]
+pub fn test_play_map_explain_move() {
+    // [2] [X]
+    // [2] [2]
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![2]),
+        MapTileData::empty(),
+        MapTileData::with_levels(vec![2]),
+        MapTileData::with_levels(vec![2]),
+    ];
+    let mut play_map = PlayMap::from_data(PlayMapData::new(2, 2, map_tiles, 16));
+    let mover = Mover::anonymous();
+
+    // blocked by a tile with nowhere to shuffle to
+    let diagnostics = play_map.explain_move_for(&mover, 0, -6, 2, Rect::new(20, 20, 8, 8));
+    assert_eq!(diagnostics.result_tuple(), (false, 0, 2, 0, 0));
+    assert_eq!(diagnostics.rejection(), Some(Rejection::Tiles));
+    let tiles: Vec<_> = diagnostics.tiles().iter().map(|tile| tile.as_tuple()).collect();
+    assert_eq!(tiles, vec![(1, 0, 2, 0, None), (1, 1, 2, 1, None)]);
+    let blocking: Vec<_> = diagnostics.blocking_tiles().iter().map(|tile| tile.as_tuple()).collect();
+    assert_eq!(blocking, vec![(1, 0, 2, 0, None)]);
+    let fallbacks: Vec<_> = diagnostics.fallbacks().iter().map(|fallback| fallback.as_tuple()).collect();
+    assert_eq!(fallbacks, vec![(Fallback::Shuffle, Some(FallbackFailure::SingleStripe))]);
+
+    // a successful shuffle
+    let diagnostics = play_map.explain_move_for(&mover, 0, -6, 2, Rect::new(12, 20, 8, 8));
+    assert_eq!(diagnostics.result_tuple(), (true, 1, 2, -2, 0));
+    assert_eq!(diagnostics.fallbacks()[0].as_tuple(), (Fallback::Shuffle, None));
+
+    // shuffles turned off or limited by the config
+    let config = MovementConfig { max_overlap: 2, ..MovementConfig::new() };
+    let diagnostics = play_map.explain_move_for(&mover.with_config(config), 0, -6, 2, Rect::new(12, 20, 8, 8));
+    assert_eq!(diagnostics.fallbacks()[0].as_tuple(), (Fallback::Shuffle, Some(FallbackFailure::Overlap)));
+    let diagnostics = play_map.explain_move_for(&mover.with_config(MovementConfig::no_assist()), 0, -6, 2, Rect::new(12, 20, 8, 8));
+    assert_eq!(diagnostics.fallbacks()[0].as_tuple(), (Fallback::Shuffle, Some(FallbackFailure::Disabled)));
+
+    // valid moves need no fallbacks
+    let diagnostics = play_map.explain_move_for(&mover, -2, 0, 2, Rect::new(12, 20, 8, 8));
+    assert_eq!(diagnostics.result_tuple(), (true, 0, 2, -2, 0));
+    assert_eq!(diagnostics.rejection(), None);
+    assert!(diagnostics.fallbacks().is_empty());
+
+    // blocked by a sprite, then a failed slide
+    play_map.insert_obstacle(1, 2, Rect::new(0, 16, 8, 16));
+    let diagnostics = play_map.explain_move_for(&mover, -4, 0, 2, Rect::new(10, 20, 8, 8));
+    assert_eq!(diagnostics.rejection(), Some(Rejection::Sprite));
+    assert!(diagnostics.blocking_tiles().is_empty());
+    let diagnostics = play_map.explain_move_for(&mover, -4, -6, 2, Rect::new(10, 20, 8, 8));
+    assert_eq!(diagnostics.result_tuple(), (false, 0, 2, 0, 0));
+    let slides: Vec<_> = diagnostics.fallbacks().iter()
+        .map(|fallback| (fallback.as_tuple(), fallback.step(), fallback.rejection(), fallback.blocking_tile()))
+        .collect();
+    assert_eq!(slides, vec![
+        ((Fallback::Slide, Some(FallbackFailure::Blocked)), Some((-4, 0)), Some(Rejection::Sprite), None),
+        ((Fallback::Slide, Some(FallbackFailure::Blocked)), Some((0, -6)), Some(Rejection::Tiles), Some((1, 0))),
+    ]);
+}
+
+#[test]
+pub fn test_play_map_ladders() {
+    // [6]     [6]
+    // [L2-6]  [X]  <- ladder on a cliff face (masked at level 6)
+    // [L2-6]  [X]
+    // [2]     [2]
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![6]),
+        MapTileData::with_levels(vec![6]),
+        MapTileData::with_masks(vec![(0, 6, false, 1)]).with_ladder(2, 6),
+        MapTileData::empty(),
+        MapTileData::empty().with_ladder(2, 6),
+        MapTileData::empty(),
+        MapTileData::with_levels(vec![2]),
+        MapTileData::with_levels(vec![2]),
+    ];
+    let play_map = PlayMap::from_data(PlayMapData::new(4, 2, map_tiles, 16));
+
+    // climb up from level 2 to level 6
+    let mut rect = Rect::new(4, 52, 8, 8);
+    let mut level = 2;
+    let mut levels = vec![];
+    while rect.top() > 4 {
+        let (valid, _, new_level, mx, my) = play_map.apply_move(0, -4, level, rect).as_tuple();
+        assert!(valid);
+        rect = rect.move_rect(mx, my);
+        level = new_level;
+        levels.push(level);
+    }
+    assert_eq!(levels, vec![2, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 6]);
+
+    // no sideways moves while climbing, and diagonals lose their sideways part
+    let rect = Rect::new(4, 28, 8, 8);
+    let (valid, _, _, _, _) = play_map.apply_move(2, 0, 4, rect).as_tuple();
+    assert_eq!(valid, false);
+    let diagnostics = play_map.explain_move_for(&Mover::anonymous(), 2, 0, 4, rect);
+    assert_eq!(diagnostics.rejection(), Some(Rejection::Ladder));
+    assert!(diagnostics.fallbacks().is_empty());
+    assert_eq!(play_map.apply_move(2, 4, 4, rect).as_tuple(), (true, 1, 3, 0, 4));
+
+    // climb back down
+    let mut rect = Rect::new(4, 4, 8, 8);
+    let mut level = 6;
+    while rect.top() < 52 {
+        let (valid, _, new_level, mx, my) = play_map.apply_move(0, 4, level, rect).as_tuple();
+        assert!(valid);
+        rect = rect.move_rect(mx, my);
+        level = new_level;
+    }
+    assert_eq!(level, 2);
+
+    // walking past the foot of the ladder is a normal move
+    assert_eq!(play_map.apply_move(4, 0, 2, Rect::new(4, 52, 8, 8)).as_tuple(), (true, 0, 2, 4, 0));
+
+    // the cliff face doesn't mask climbers, only sprites at the bottom level
+    let rect = Rect::new(4, 28, 8, 8);
+    assert!(play_map.get_sprite_masks(rect, get_z_index(rect, 4, 16), 4, true).is_empty());
+    let masks = play_map.get_sprite_masks(rect, get_z_index(rect, 2, 16), 2, true);
+    assert_eq!(masks, vec![TileMasks::new(0, 1, vec![0])]);
+
+    // [2] [4] [L2-6]  <- only the ends of a ladder can be walked onto
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![2]),
+        MapTileData::with_levels(vec![4]),
+        MapTileData::empty().with_ladder(2, 6),
+    ];
+    let play_map = PlayMap::from_data(PlayMapData::new(1, 3, map_tiles, 16));
+    let (valid, _, _, _, _) = play_map.apply_move(8, 0, 4, Rect::new(20, 4, 8, 8)).as_tuple();
+    assert_eq!(valid, false);
+    let (valid, _, _, _, _) = play_map.apply_move(16, 0, 4, Rect::new(20, 4, 8, 8)).as_tuple();
+    assert_eq!(valid, false);
+    assert_eq!(play_map.is_move_valid(2, Rect::new(36, 4, 8, 8)), (true, 2));
+    assert_eq!(play_map.is_move_valid(6, Rect::new(36, 4, 8, 8)), (true, 6));
+}
+
+#[test]
+pub fn test_play_map_water() {
+    // [2] [W2 4px] [W2 12px] [W2 12px]
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![2]),
+        MapTileData::empty().with_water(2, 4),
+        MapTileData::empty().with_water(2, 12),
+        MapTileData::empty().with_water(2, 12),
+    ];
+    let mut play_map = PlayMap::from_data(PlayMapData::new(1, 4, map_tiles, 16));
+
+    // only swimmers can get in
+    let (valid, _, _, _, _) = play_map.apply_move(2, 0, 2, Rect::new(8, 4, 8, 8)).as_tuple();
+    assert_eq!(valid, false);
+    let swimmer = Mover::anonymous().with_swim(true);
+    let result = play_map.apply_move_for(&swimmer, 2, 0, 2, Rect::new(8, 4, 8, 8));
+    assert_eq!(result.as_tuple(), (true, 0, 2, 2, 0));
+
+    play_map.insert_sprite(1, 2, Rect::new(8, 4, 8, 8));
+    let (valid, _, _, _, _) = play_map.apply_sprite_move(1, 2, 0).as_tuple();
+    assert_eq!(valid, false);
+    assert!(play_map.set_sprite_can_swim(1, true));
+    let (valid, _, _, _, _) = play_map.apply_sprite_move(1, 2, 0).as_tuple();
+    assert!(valid);
+
+    // deep water raises an event once the whole base rect is in it
+    assert!(play_map.get_event(2, Rect::new(20, 4, 8, 8)).is_none());
+    assert!(play_map.get_event(2, Rect::new(28, 4, 8, 8)).is_none());
+    assert_eq!(play_map.get_event(2, Rect::new(36, 4, 8, 8)).unwrap().as_tuple(), (2, 12));
+    assert_eq!(play_map.get_event(2, Rect::new(44, 4, 8, 8)).unwrap().as_tuple(), (2, 12));
+    assert!(play_map.get_event(4, Rect::new(36, 4, 8, 8)).is_none());
+
+    // sprites in the water are masked below the waterline
+    let rect = Rect::new(28, 4, 8, 8);
+    let masks = play_map.get_sprite_masks(rect, get_z_index(rect, 2, 16), 2, true);
+    assert_eq!(masks, vec![
+        TileMasks::with_waterline(1, 0, vec![], 8),
+        TileMasks::with_waterline(2, 0, vec![], 0),
+    ]);
+    assert!(play_map.get_sprite_masks(rect, get_z_index(rect, 4, 16), 4, true).is_empty());
+}
+
+#[test]
+pub fn test_play_map_capabilities() {
+    let walker = Capabilities::new();
+
+    // [2] [X] [6]  <- flyers cross walls at their level, but not higher ground
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![2]),
+        MapTileData::empty(),
+        MapTileData::with_levels(vec![6]),
+    ];
+    let mut play_map = PlayMap::from_data(PlayMapData::new(1, 3, map_tiles, 16));
+    let flyer = Capabilities { can_fly: true, ..Capabilities::new() };
+    let (valid, _, _, _, _) = play_map.apply_move_with_capabilities(2, 0, 2, Rect::new(8, 4, 8, 8), &walker).as_tuple();
+    assert_eq!(valid, false);
+    let result = play_map.apply_move_with_capabilities(2, 0, 2, Rect::new(8, 4, 8, 8), &flyer);
+    assert_eq!(result.as_tuple(), (true, 0, 2, 2, 0));
+    let (valid, _, _, _, _) = play_map.apply_move_with_capabilities(2, 0, 2, Rect::new(24, 4, 8, 8), &flyer).as_tuple();
+    assert_eq!(valid, false);
+
+    // set per sprite
+    play_map.insert_sprite(1, 2, Rect::new(8, 4, 8, 8));
+    let (valid, _, _, _, _) = play_map.apply_sprite_move(1, 2, 0).as_tuple();
+    assert_eq!(valid, false);
+    assert!(play_map.set_sprite_capabilities(1, &flyer));
+    let (valid, _, _, _, _) = play_map.apply_sprite_move(1, 2, 0).as_tuple();
+    assert!(valid);
+
+    // [2] [S3] [D2-2]
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![2]),
+        MapTileData::with_special_levels(vec![3]),
+        MapTileData::with_down_levels(vec![(2, 2)]),
+    ];
+    let play_map = PlayMap::from_data(PlayMapData::new(1, 3, map_tiles, 16));
+
+    // keeping off the stairs
+    let no_stairs = Capabilities { can_use_stairs: false, ..Capabilities::new() };
+    let result = play_map.apply_move_with_capabilities(2, 0, 3, Rect::new(18, 4, 8, 8), &walker);
+    assert_eq!(result.as_tuple(), (true, 0, 3, 2, 0));
+    let (valid, _, _, _, _) = play_map.apply_move_with_capabilities(2, 0, 3, Rect::new(18, 4, 8, 8), &no_stairs).as_tuple();
+    assert_eq!(valid, false);
+
+    // keeping out of holes
+    let no_holes = Capabilities { ignores_down_levels: true, ..Capabilities::new() };
+    let result = play_map.apply_move_with_capabilities(2, 0, 2, Rect::new(34, 4, 8, 8), &walker);
+    assert_eq!(result.as_tuple(), (true, 0, 2, 2, 0));
+    let (valid, _, _, _, _) = play_map.apply_move_with_capabilities(2, 0, 2, Rect::new(34, 4, 8, 8), &no_holes).as_tuple();
+    assert_eq!(valid, false);
+
+    // [2] [4]  <- a step up
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![2]),
+        MapTileData::with_levels(vec![4]),
+    ];
+    let play_map = PlayMap::from_data(PlayMapData::new(1, 2, map_tiles, 16));
+    let small_step = Capabilities { max_step_up: 1, ..Capabilities::new() };
+    let step = Capabilities { max_step_up: 2, ..Capabilities::new() };
+    let (valid, _, _, _, _) = play_map.apply_move_with_capabilities(2, 0, 2, Rect::new(8, 4, 8, 8), &walker).as_tuple();
+    assert_eq!(valid, false);
+    let (valid, _, _, _, _) = play_map.apply_move_with_capabilities(2, 0, 2, Rect::new(8, 4, 8, 8), &small_step).as_tuple();
+    assert_eq!(valid, false);
+    let result = play_map.apply_move_with_capabilities(16, 0, 2, Rect::new(4, 4, 8, 8), &step);
+    assert_eq!(result.as_tuple(), (true, 0, 4, 16, 0));
+    // and back down again
+    let result = play_map.apply_move_with_capabilities(-16, 0, 4, Rect::new(20, 4, 8, 8), &step);
+    assert_eq!(result.as_tuple(), (true, 0, 2, -16, 0));
+    // but never straddling the step, as the whole span has to be at the same level
+    let (valid, _, _, _, _) = play_map.apply_move_with_capabilities(2, 0, 2, Rect::new(8, 4, 8, 8), &step).as_tuple();
+    assert_eq!(valid, false);
+    let (valid, _, _, _, _) = play_map.apply_move_with_capabilities(-2, 0, 4, Rect::new(16, 4, 8, 8), &step).as_tuple();
+    assert_eq!(valid, false);
+}
+
+#[test]
+pub fn test_parse_level() {
+    assert_eq!(parse_level("4"), Ok(Level::Default(8)));
+    assert_eq!(parse_level("-1"), Ok(Level::Default(-2)));
+    assert_eq!(parse_level("S3"), Ok(Level::Special(6)));
+    assert_eq!(parse_level("S3.5"), Ok(Level::Special(7)));
+    assert_eq!(parse_level("D6-4"), Ok(Level::Down(12, 8)));
+    assert!(parse_level("X").is_err());
+    assert!(parse_level("4a").is_err());
+    assert!(parse_level("D6").is_err());
+    assert!(parse_level("D6-0").is_err());
+    assert!(parse_level("100").is_err());
+    assert!(parse_level("9223372036854775807").is_err());
+    assert!(parse_level("D1-9223372036854775807").is_err());
+    assert_eq!(parse_mask_level("3"), Ok((6, true)));
+    assert_eq!(parse_mask_level("V2"), Ok((4, false)));
+    assert!(parse_mask_level("Vx").is_err());
+    assert!(parse_mask_level("V-9223372036854775807").is_err());
+}
+
+#[test]
+pub fn test_play_map_extreme_levels() {
+    // levels at the ends of the i8 range shouldn't overflow
+    let play_map = PlayMap::from_data(PlayMapData::from_ascii("[S127] [S126] [D126-2]\n[S-128] [-128] [127]", 16).unwrap());
+    assert_eq!(play_map.is_move_valid(127, Rect::new(0, 0, 32, 16)), (true, 126));
+    assert_eq!(play_map.is_move_valid(-128, Rect::new(0, 16, 32, 16)), (true, -128));
+    assert_eq!(play_map.lint_stairs().len(), 1);
+}
+
+#[test]
+pub fn test_map_def_check() {
+    // [1]    [S1] [S1.5] [S2]
+    // [D2-1]  .     .    [2]
+    // [1]     .     .     .
+    let map_def: MapDef = serde_json::from_str(r#"{
+        "name": "test", "rows": 3, "cols": 4,
+        "mapTiles": [
+            {"xy": [0, 0], "tiles": [{"tileSet": "grass", "tile": "grass1", "maskLevel": "1"}], "levels": ["1"]},
+            {"xy": [1, 0], "tiles": [], "levels": ["S1"]},
+            {"xy": [2, 0], "tiles": [], "levels": ["S1.5"]},
+            {"xy": [3, 0], "tiles": [], "levels": ["S2"]},
+            {"xy": [0, 1], "tiles": [], "levels": ["D2-1"]},
+            {"xy": [3, 1], "tiles": [], "levels": ["2"]},
+            {"xy": [0, 2], "tiles": [], "levels": ["1"]}
+        ]
+    }"#).unwrap();
+    assert_eq!(map_def.check(None), vec![]);
+
+    let play_map = PlayMap::from_data(map_def.to_play_map_data(16));
+    let (valid, _, level, _, _) = play_map.apply_move(8, 0, 2, Rect::new(24, 4, 8, 8)).as_tuple();
+    assert!(valid);
+    assert_eq!(level, 3);
+
+    let tile_sets: HashMap<String, TileSetDef> = vec![
+        ("grass".to_string(), serde_json::from_str(r#"{"name": "grass", "tiles": [{"name": "grass2"}]}"#).unwrap())
+    ].into_iter().collect();
+    let problems: Vec<String> = map_def.check(Some(&tile_sets)).iter().map(|p| p.to_string()).collect();
+    assert_eq!(problems, vec!["[0, 0] unknown tile: grass/grass1"]);
+
+    let map_def: MapDef = serde_json::from_str(r#"{
+        "name": "test", "rows": 2, "cols": 2,
+        "mapTiles": [
+            {"xy": [0, 0], "tiles": [{"tileSet": "grass", "tile": "grass1", "maskLevel": "?"}], "levels": ["S4", "D3-1", "Q"]},
+            {"xy": [1, 0], "tiles": [], "levels": ["1"]},
+            {"xy": [1, 0], "tiles": [], "levels": ["1"]},
+            {"xy": [2, 0], "tiles": [], "levels": ["1"]}
+        ]
+    }"#).unwrap();
+    let problems: Vec<String> = map_def.check(None).iter().map(|p| p.to_string()).collect();
+    assert_eq!(problems, vec![
+        "[1, 0] map tile is defined more than once",
+        "[2, 0] map tile is outside the map",
+        "[0, 0] unknown level: Q",
+        "[0, 0] unknown mask level: ?",
+        "[0, 0] stairs bottom at S4 doesn't touch anything walkable at level 4",
+        "[0, 0] down level D3-1 drops to level 2 which doesn't exist below",
+    ]);
+}
+
+#[test]
+pub fn test_play_map_lint_stairs() {
+    assert_eq!(an_example_play_map().lint_stairs(), vec![]);
+
+    // [4] [S4] [X]  <- level 4 only on the left
+    // [X] [S3] [X]
+    // [X] [S2] [X]  <- no way off at the bottom
+    // [X]  [X] [X]
+    // [2] [S3] [2]  <- a loose half step
+    let map_tiles = vec![
+        MapTileData::with_levels(vec![4]),
+        MapTileData::with_special_levels(vec![4]),
+        MapTileData::empty(),
+        MapTileData::empty(),
+        MapTileData::with_special_levels(vec![3]),
+        MapTileData::empty(),
+        MapTileData::empty(),
+        MapTileData::with_special_levels(vec![2]),
+        MapTileData::empty(),
+        MapTileData::empty(),
+        MapTileData::empty(),
+        MapTileData::empty(),
+        MapTileData::with_levels(vec![2]),
+        MapTileData::with_special_levels(vec![3]),
+        MapTileData::with_levels(vec![2]),
+    ];
+    let play_map = PlayMap::from_data(PlayMapData::new(5, 3, map_tiles, 16));
+    let broken_stairs = play_map.lint_stairs();
+    assert_eq!(broken_stairs.len(), 2);
+    assert_eq!(broken_stairs[0], BrokenStair::new(StairEnd::Bottom, 2, StairFault::Unconnected, vec![(1, 2)]));
+    assert_eq!(broken_stairs[1], BrokenStair::new(StairEnd::Bottom, 3, StairFault::HalfLevel, vec![(1, 4)]));
+    assert_eq!(broken_stairs[0].to_string(), "stairs bottom at [1, 2] doesn't touch anything walkable at level 2");
+
+    // missing tiles have no stairs and nothing walkable
+    let play_map = PlayMap::from_data(PlayMapData::new(2, 2, vec![MapTileData::with_special_levels(vec![2])], 16));
+    let broken_stairs = play_map.lint_stairs();
+    assert_eq!(broken_stairs, vec![BrokenStair::new(StairEnd::Bottom, 2, StairFault::Unconnected, vec![(0, 0)])]);
+}
+
+#[test]
+pub fn test_play_map_ascii() {
+    let ascii = "[4] [S4] [4]\n[X] [S3] [X]\n[2] [S3] [2]\n[2] [S2] [2]\n";
+    assert_eq!(an_example_play_map().to_ascii(None), ascii);
+
+    // comments parse as they are, and dumps parse back to the same map
+    let play_map = PlayMap::from_data(PlayMapData::from_ascii("
+        // [4] [S4] [4]  <- level 4
+        // [X] [S3] [X]  <- top of steps + wall on either side
+        // [2] [S3] [2]  <- bottom of steps + level 2 on either side
+        // [2] [S2] [2]  <- level 2
+    ", 16).unwrap());
+    assert_eq!(play_map.to_ascii(None), ascii);
+    let result = play_map.apply_move(0, -1, 2, Rect::new(20, 33, 8, 8));
+    assert_eq!(result.as_tuple(), (true, 0, 3, 0, -1));
+
+    let ascii = "[6]   [X,m4] [X,mV2]\n[D6-4] [S3,S5] [1,2]\n";
+    let play_map = PlayMap::from_data(PlayMapData::from_ascii(ascii, 16).unwrap());
+    let masks = play_map.get_sprite_masks(Rect::new(20, 4, 8, 8), 40, 2, true);
+    assert_eq!(masks, vec![TileMasks::new(1, 0, vec![0])]);
+    assert_eq!(play_map.to_ascii(None), "[6]    [X,m4]  [X,mV2]\n[D6-4] [S3,S5] [1,2]\n");
+
+    // masks keep the tile layer they're on
+    let ascii = "[6]    [X,m4@2] [X,mV2]\n[D6-4] [S3,S5]  [1,2]\n";
+    let play_map = PlayMap::from_data(PlayMapData::from_ascii(ascii, 16).unwrap());
+    let masks = play_map.get_sprite_masks(Rect::new(20, 4, 8, 8), 40, 2, true);
+    assert_eq!(masks, vec![TileMasks::new(1, 0, vec![2])]);
+    assert_eq!(play_map.to_ascii(None), ascii);
+    assert!(PlayMapData::from_ascii("[X,m4@]", 16).is_err());
+
+    // runtime levels and the actor
+    let mut play_map = an_example_play_map();
+    play_map.add_level_to_tile(0, 1, 4);
+    let ascii = play_map.to_ascii(Some((&Rect::new(4, 20, 16, 8), 4)));
+    assert_eq!(ascii, "[4]  [S4] [4]\n(+4) (S3) [X]\n[2]  [S3] [2]\n[2]  [S2] [2]\n@ level 4 at (4, 20) 16x8\n");
+    play_map.rollback_tile(0, 1);
+    assert!(play_map.to_ascii(None).starts_with("[4] [S4] [4]\n[X] [S3] [X]\n"));
+
+    // the rest of the tile data parses back too
+    let ascii = "[2,I]    [2,C-2:0] [2,%50] [L2-6]\n[2,E2tl] [2,F2rb]  [W2-8]  [X,m4]\n";
+    let play_map = PlayMap::from_data(PlayMapData::from_ascii(ascii, 16).unwrap());
+    assert_eq!(play_map.to_ascii(None), ascii);
+    assert!(play_map.get_event(2, Rect::new(36, 20, 8, 8)).is_some());
+    assert_eq!(play_map.is_step_valid(2, Rect::new(18, 20, 8, 8), Rect::new(14, 20, 8, 8)), (false, 2));
+
+    // platform levels parse back as plain levels
+    let mut play_map = PlayMap::from_data(PlayMapData::from_ascii("[X] [2]", 16).unwrap());
+    play_map.add_platform(1, 2, Rect::new(0, 0, 16, 16), 1);
+    let ascii = play_map.to_ascii(None);
+    assert_eq!(ascii, "[P2] [2]\n");
+    assert_eq!(PlayMap::from_data(PlayMapData::from_ascii(&ascii, 16).unwrap()).to_ascii(None), "[2] [2]\n");
+
+    assert!(PlayMapData::from_ascii("[2,E2q]", 16).is_err());
+    assert!(PlayMapData::from_ascii("[2,C2]", 16).is_err());
+    assert!(PlayMapData::from_ascii("[L2]", 16).is_err());
+
+    // a short tile list dumps the missing tiles as walls
+    let play_map = PlayMap::from_data(PlayMapData::new(2, 2, vec![MapTileData::with_levels(vec![2])], 16));
+    assert_eq!(play_map.to_ascii(None), "[2] [X]\n[X] [X]\n");
+
+    assert!(PlayMapData::from_ascii("[2] [2]\n[2]", 16).is_err());
+    assert!(PlayMapData::from_ascii("[2] [Q2]", 16).is_err());
+    assert!(PlayMapData::from_ascii("[D2]", 16).is_err());
+    assert!(PlayMapData::from_ascii("  ", 16).is_err());
+}
+
+#[test]
+pub fn test_play_map_record_and_replay() {
+    let mut play_map = an_example_play_map_with_down_levels();
+    assert!(play_map.start_recording());
+    play_map.apply_move(0, 2, 6, Rect::new(20, 14, 8, 16));
+    play_map.get_event(6, Rect::new(20, 16, 8, 16));
+    play_map.add_level_to_tile(1, 1, 6);
+    play_map.get_event(6, Rect::new(20, 16, 8, 16));
+    play_map.rollback_tile(1, 1);
+    play_map.apply_move(-2, 0, 6, Rect::new(16, 4, 8, 8));
+    let recording = play_map.stop_recording().unwrap();
+    assert_eq!(recording, "\
+t 16
+m 0 2 6 20 14 8 16 = 1 0 6 0 2
+e 6 20 16 8 16 = 1 4
+a 1 1 6
+e 6 20 16 8 16 = 1 4
+r 1 1
+m -2 0 6 16 4 8 8 = 1 0 6 -2 0
+");
+    assert!(play_map.stop_recording().is_none());
+
+    // recording has to start from the map as loaded
+    play_map.add_level_to_tile(1, 1, 6);
+    assert!(!play_map.start_recording());
+    let mut play_map = an_example_play_map_with_down_levels();
+    play_map.insert_sprite(1, 6, Rect::new(4, 4, 8, 8));
+    assert!(!play_map.start_recording());
+    assert!(play_map.stop_recording().is_none());
+
+    // replays against a fresh map
+    let replay = Replay::parse(&recording).unwrap();
+    assert_eq!(replay.tile_size(), Some(16));
+    assert_eq!(replay.calls().len(), 6);
+    assert_eq!(replay.to_string(), recording);
+    assert_eq!(replay.run(&mut an_example_play_map_with_down_levels()), Ok(()));
+
+    // and flags the first call that comes out differently
+    let replay = Replay::parse("
+        # falls through the hole
+        m 0 2 6 20 14 8 16 = 1 0 6 0 2
+        e 6 20 16 8 16 = -
+        m 0 2 6 20 16 8 16 = 1 0 6 0 2
+    ").unwrap();
+    let mismatch = replay.run(&mut an_example_play_map_with_down_levels()).unwrap_err();
+    assert_eq!(mismatch.index, 1);
+    assert_eq!(mismatch.actual, ReplayCall::get_event(6, &Rect::new(20, 16, 8, 16), Some((1, 4))));
+    assert_eq!(mismatch.to_string(), "call 1 differs: expected `e 6 20 16 8 16 = -`, got `e 6 20 16 8 16 = 1 4`");
+
+    assert!(Replay::parse("m 0 2 6 20 14 8 16").is_err());
+    assert!(Replay::parse("e 6 20 16 8 16 = 1").is_err());
+    assert_eq!(Replay::parse("a 1 1\n").unwrap_err(), "line 1: unknown call: a 1 1");
+    assert_eq!(Replay::parse("r 1 1\nt 16").unwrap_err(), "line 2: the tile size has to come first");
+}
+
+#[test]
+pub fn test_simulation() {
+    // holding right for 30 frames stops at the edge of the map
+    let play_map = PlayMap::from_data(PlayMapData::from_ascii("[2] [2] [2] [2]", 16).unwrap());
+    let actor = Actor::with_base_rect(2, Rect::new(4, 4, 8, 8), Rect::new(4, 4, 8, 8));
+    let mut simulation = Simulation::new(play_map, actor);
+    let frames = simulation.run(&[Key::RIGHT as u8; 30]);
+    assert_eq!(frames[0].as_tuple(), (6, 4, 2));
+    assert_eq!(frames[25].as_tuple(), (56, 4, 2));
+    assert_eq!(frames[29].as_tuple(), (56, 4, 2));
+    assert_eq!(simulation.actor().rect().top_left(), (56, 4));
+
+    // diagonals are deferred every other move, so they cover two steps in three frames
+    let play_map = PlayMap::from_data(PlayMapData::from_ascii("
+        [2] [2] [2] [2]
+        [2] [2] [2] [2]
+        [2] [2] [2] [2]
+    ", 16).unwrap());
+    let mut simulation = Simulation::new(play_map, Actor::with_base_rect(2, Rect::new(4, 4, 8, 8), Rect::new(4, 4, 8, 8)));
+    let frames = simulation.run(&[Key::RIGHT as u8 | Key::DOWN as u8; 6]);
+    let positions: Vec<(i32, i32, i8)> = frames.iter().map(|frame| frame.as_tuple()).collect();
+    assert_eq!(positions, vec![(4, 4, 2), (6, 6, 2), (8, 8, 2), (8, 8, 2), (10, 10, 2), (12, 12, 2)]);
+
+    // walking into the hole falls two tiles, a level at a time, then carries on
+    let actor = Actor::with_base_rect(6, Rect::new(20, 4, 8, 8), Rect::new(20, 4, 8, 8));
+    let mut simulation = Simulation::new(an_example_play_map_with_down_levels(), actor);
+    let frames = simulation.run(&[Key::DOWN as u8; 16]);
+    assert_eq!(frames[4].as_tuple(), (20, 14, 6));
+    assert!(!frames[4].falling);
+    assert_eq!(frames[5].as_tuple(), (20, 16, 6));
+    assert!(frames[5].falling);
+    assert_eq!(frames[6].as_tuple(), (20, 20, 4));
+    assert_eq!(frames[10].as_tuple(), (20, 36, 2));
+    assert_eq!(frames[13].as_tuple(), (20, 48, 2));
+    assert!(!frames[13].falling);
+    assert_eq!(frames[15].as_tuple(), (20, 52, 2));
+
+    // the sprite rect hangs above the base rect, as for the player
+    let actor = Actor::new(2, Rect::new(0, 0, 16, 32));
+    assert_eq!(actor.base_rect().top_left(), (0, 16));
+    assert_eq!(actor.base_rect().height(), 18);
+}
+
+/*
+#[test]
+pub fn test_map_tile_levels() {
+    let map_tile = MapTile::with_levels(vec![4, 6]);
+    let (inc, level) = map_tile.get_validity_of(4);
+    assert_eq!(inc, 1);
+    assert!(level.is_none());
+    let (inc, level) = map_tile.get_validity_of(6);
+    assert_eq!(inc, 1);
+    assert!(level.is_none());
+    let (inc, level) = map_tile.get_validity_of(8);
+    assert_eq!(inc, 0);
+    assert!(level.is_none());
+}
+
+#[test]
+pub fn test_map_tile_down_levels() {
+    let map_tile = MapTile::with_down_levels(vec![(2, 4)]);
+    let (inc, level) = map_tile.get_validity_of(2);
+    assert_eq!(inc, 1);
+    assert!(level.is_none());
+    let (inc, level) = map_tile.get_validity_of(4);
+    assert_eq!(inc, 0);
+    assert!(level.is_none());
+}
+
+#[test]
+pub fn test_map_tile_add_new_level() {
+    let map_tile_data = MapTileData::with_levels(vec![1, 2, 3]);
+    let mut map_tile = MapTile::from_data(map_tile_data);
+    let (inc, level) = map_tile.get_validity_of(3);
+    assert_eq!(inc, 1);
+    assert!(level.is_none());
+    let (inc, level) = map_tile.get_validity_of(4);
+    assert_eq!(inc, 0);
+    assert!(level.is_none());
+
+    map_tile.add_level(4);
+    let (inc, level) = map_tile.get_validity_of(4);
+    assert_eq!(inc, 1);
+    assert!(level.is_none());
+
+    map_tile.rollback();
+    let (inc, level) = map_tile.get_validity_of(4);
+    assert_eq!(inc, 0);
+    assert!(level.is_none());
+}
+*/
\ No newline at end of file
@@ -0,0 +1,128 @@
+//! Property tests for the movement invariants, over random maps and moves.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+extern crate wasm_ulmo_map;
+use wasm_ulmo_map::{PlayMap, PlayMapData, Rect, MovementConfig, MapDef};
+
+use proptest::prelude::*;
+
+const TILE_SIZE: u32 = 16;
+
+const CELLS: [&str; 12] = ["X", "2", "2", "2", "4", "2,4", "S2", "S3", "S4", "D4-2", "D6-4", "6"];
+
+// maps are generated as from_ascii grids, so failures print something readable
+fn ascii_map() -> impl Strategy<Value = String> {
+    (1usize..6, 1usize..6).prop_flat_map(|(rows, cols)| {
+        prop::collection::vec(prop::sample::select(CELLS.to_vec()), rows * cols).prop_map(move |cells| {
+            cells.chunks(cols)
+                .map(|row| row.iter().map(|cell| format!("[{}]", cell)).collect::<Vec<String>>().join(" "))
+                .collect::<Vec<String>>()
+                .join("\n")
+        })
+    })
+}
+
+// rects can start off the map and run past its edges
+fn rect() -> impl Strategy<Value = (i32, i32, u32, u32)> {
+    (-40i32..120, -40i32..120, 1u32..40, 1u32..40)
+}
+
+fn play_map(ascii: &str) -> PlayMap {
+    PlayMap::from_data(PlayMapData::from_ascii(ascii, TILE_SIZE).unwrap())
+}
+
+// the part of the moved rect in the stripe a shuffle by (dx, dy) heads for: the first column or
+// row of tiles for a negative nudge, and the second for a positive one
+fn open_stripe_rect(ascii: &str, rect: &Rect, dx: i8, dy: i8) -> Rect {
+    let rows = ascii.lines().count() as u8;
+    let cols = ascii.lines().next().unwrap().split_whitespace().count() as u8;
+    let (tx1, ty1, _, _) = rect.to_tile_range(TILE_SIZE, cols, rows).as_tuple();
+    let ts = TILE_SIZE as i32;
+    if dx != 0 {
+        let left = (tx1 as i32 + (dx > 0) as i32) * ts;
+        Rect::from_edges(rect.left().max(left), rect.top(), rect.right().min(left + ts), rect.bottom())
+    }
+    else {
+        let top = (ty1 as i32 + (dy > 0) as i32) * ts;
+        Rect::from_edges(rect.left(), rect.top().max(top), rect.right(), rect.bottom().min(top + ts))
+    }
+}
+
+proptest! {
+    // moves are only checked from valid positions, which rules out a fair few generated cases
+    #![proptest_config(ProptestConfig { max_global_rejects: 1 << 16, ..ProptestConfig::default() })]
+
+    #[test]
+    fn apply_move_lands_on_a_valid_span(ascii in ascii_map(), (x, y, w, h) in rect(), mx in -4i8..=4, my in -4i8..=4, level in 0i8..8) {
+        let play_map = play_map(&ascii);
+        let rect = Rect::new(x, y, w, h);
+        prop_assume!(play_map.is_move_valid(level, rect).0);
+        let result = play_map.apply_move(mx, my, level, rect);
+        let (dx, dy) = result.deltas();
+        if result.valid && (mx == 0 || my == 0) && result.deferral == 1 {
+            // a shuffle only nudges the mover towards the open stripe, and can leave it over tiles
+            // that aren't: the level is the one that stripe of the moved rect is valid at
+            let stripe_rect = open_stripe_rect(&ascii, &rect.move_rect(mx, my), dx, dy);
+            prop_assert_eq!(play_map.is_move_valid(level, stripe_rect), (true, result.level),
+                "shuffled by ({}, {}) to level {}", dx, dy, result.level);
+        }
+        else if result.valid {
+            let (valid, _) = play_map.is_move_valid(result.level, rect.move_rect(dx, dy));
+            prop_assert!(valid, "moved by ({}, {}) to level {}", dx, dy, result.level);
+        }
+    }
+
+    #[test]
+    fn apply_move_deltas_are_bounded(ascii in ascii_map(), (x, y, w, h) in rect(), mx in -4i8..=4, my in -4i8..=4, level in -2i8..10) {
+        let play_map = play_map(&ascii);
+        let nudge = MovementConfig::new().nudge as i8;
+        let (dx, dy) = play_map.apply_move(mx, my, level, Rect::new(x, y, w, h)).deltas();
+        prop_assert!(dx.abs() <= mx.abs() + nudge, "dx {} for mx {}", dx, mx);
+        prop_assert!(dy.abs() <= my.abs() + nudge, "dy {} for my {}", dy, my);
+    }
+
+    #[test]
+    fn map_queries_dont_panic(ascii in ascii_map(), (x, y, w, h) in rect(), level in -2i8..10, z in -100i32..200) {
+        let play_map = play_map(&ascii);
+        let rect = Rect::new(x, y, w, h);
+        play_map.get_event(level, rect);
+        play_map.get_sprite_masks(rect, z, level, true);
+        play_map.get_sprite_masks(rect, z, level, false);
+        play_map.get_jump(2, 0, 24, level, rect);
+        play_map.to_ascii(Some((&rect, level)));
+        play_map.lint_stairs();
+    }
+
+    #[test]
+    fn tile_ranges_stay_on_the_map((x, y, w, h) in rect(), rows in 1u8..6, cols in 1u8..6) {
+        let (tx1, ty1, tx2, ty2) = Rect::new(x, y, w, h).to_tile_range(TILE_SIZE, cols, rows).as_tuple();
+        prop_assert!(tx1 <= tx2 && tx2 <= cols);
+        prop_assert!(ty1 <= ty2 && ty2 <= rows);
+    }
+}
+
+// anything that parses has to be safe to load and query, whatever the levels
+proptest! {
+    #[test]
+    fn ascii_maps_dont_panic(ascii in "[\\[\\]SDXm0-9,\\- \n]{0,48}", (x, y, w, h) in rect(), level in any::<i8>(), mx in any::<i8>(), my in any::<i8>()) {
+        if let Ok(play_map_data) = PlayMapData::from_ascii(&ascii, TILE_SIZE) {
+            let play_map = PlayMap::from_data(play_map_data);
+            let rect = Rect::new(x, y, w, h);
+            play_map.apply_move(mx, my, level, rect);
+            play_map.get_event(level, rect);
+            play_map.to_ascii(Some((&rect, level)));
+            play_map.lint_stairs();
+        }
+    }
+
+    #[test]
+    fn map_files_dont_panic(levels in prop::collection::vec("(S|D)?-?[0-9]{1,3}(\\.5)?(--?[0-9]{1,3})?", 0..4), x in -1i32..4, y in -1i32..4) {
+        let json = format!(r#"{{"name": "test", "rows": 3, "cols": 3, "mapTiles": [
+            {{"xy": [{}, {}], "tiles": [], "levels": {:?}}},
+            {{"xy": [1, 1], "tiles": [], "levels": ["S1", "2"]}}
+        ]}}"#, x, y, levels);
+        let map_def: MapDef = serde_json::from_str(&json).unwrap();
+        map_def.check(None);
+    }
+}
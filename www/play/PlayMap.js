@@ -1,6 +1,6 @@
 import { tileSize, viewWidth, viewHeight } from '../config';
 import { drawTile, initTile, parseLevel, Rect } from '../utils';
-import { WasmPlayMap } from 'wasm-ulmo-map';
+import { Capabilities, WasmPlayMap } from 'wasm-ulmo-map';
 
 const blackTile = initTile('black');
 
@@ -176,6 +176,25 @@ class PlayMap {
         return [valid, deferral, level / 2, mx, my];
     }
 
+    // capabilities has any of canFly, canSwim, ignoresDownLevels, canUseStairs and maxStepUp,
+    // which is in levels like levelIn and gets doubled the same way
+    applyMoveWithCapabilities(mxIn, myIn, levelIn, baseRect, capabilities) {
+        const wasmCapabilities = new Capabilities();
+        ['canFly', 'canSwim', 'ignoresDownLevels', 'canUseStairs'].forEach(flag => {
+            if (capabilities[flag] !== undefined) {
+                wasmCapabilities[flag] = capabilities[flag];
+            }
+        });
+        if (capabilities.maxStepUp !== undefined) {
+            wasmCapabilities.maxStepUp = Math.round(capabilities.maxStepUp * 2);
+        }
+        const { valid, deferral, level, mx, my } = this.wasmPlayMap.applyMoveWithCapabilities(
+            mxIn, myIn, Math.round(levelIn * 2), baseRect.toWasmRect(), wasmCapabilities
+        );
+        wasmCapabilities.free();
+        return [valid, deferral, level / 2, mx, my];
+    }
+
     getMasks(spriteRect, spriteLevel, spriteZ, spriteUpright) {
         const masks = this.wasmPlayMap.getSpriteMasks(
             spriteRect.toWasmRect(),
@@ -202,6 +221,12 @@ class PlayMap {
                     downLevel: value / 2
                 };
             }
+            if (eventType === 2) {
+                return {
+                    eventType: 'deepWater',
+                    depth: value
+                };
+            }
         }
         return null;
     }
@@ -213,6 +238,20 @@ class PlayMap {
     rollbackTile(tx, ty) {
         this.wasmPlayMap.rollbackTile(tx, ty);
     }
+
+    // for bug reports: the text returned replays with `ulmo-map replay`. Recording only starts
+    // on a map as loaded, so this returns false once levels have been added or sprites placed
+    startRecording() {
+        const started = this.wasmPlayMap.startRecording();
+        if (!started) {
+            console.log('recording not started: the map has changed since it was loaded');
+        }
+        return started;
+    }
+
+    stopRecording() {
+        return this.wasmPlayMap.stopRecording();
+    }
 }
 
 export default PlayMap;